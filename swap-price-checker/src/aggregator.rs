@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use crate::error::SwapError;
+use crate::exchange::{Exchange, Network};
+use crate::gas::GasModel;
+use crate::pricing::SwapQuote;
+use crate::router::{SwapLeg, find_best_split_with_route, order_gas_units, order_quote};
+use crate::token::{TokenAmount, TokenType};
+use crate::venue::Venue;
+
+//gas an aggregator's own contract uses when the config doesn't say
+pub const DEFAULT_AGGREGATOR_GAS_UNITS: u64 = 40_000;
+
+//A meta-venue like Matcha. It holds no liquidity, it sources a swap from the venues on its
+//network and takes a fee on what they pay out. Its routing goes further than the built-in
+//one; an order can be split between the direct pools and a route through other tokens at
+//the same time, all in one transaction.
+#[derive(Debug, Clone)]
+pub struct Aggregator {
+
+    pub exchange_name: Exchange,
+    pub network: Network,
+    pub fee_bps: u32,        //taken from the output, rounded up
+    pub gas_units: u64,      //its own contract's gas, on top of the venues' it goes through
+    pub collected_fees: HashMap<TokenType, TokenAmount>,
+}
+
+//what going through the aggregator would pay, worked out before anything is executed
+#[derive(Debug, Clone)]
+pub struct AggregatorQuote {
+
+    pub legs: Vec<SwapLeg>,          //what the aggregator sends to each venue
+    pub quote: SwapQuote,            //after the aggregator's fee
+    pub fee: TokenAmount,            //in the token received
+    pub gas_units: u64,              //of every leg, plus the aggregator's own
+}
+
+impl Aggregator {
+
+    pub fn new(exchange_name: Exchange, network: Network, fee_bps: u32, gas_units: u64) -> Self {
+
+        Aggregator {
+            exchange_name,
+            network,
+            fee_bps,
+            gas_units,
+            collected_fees: HashMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+
+        self.exchange_name.as_str()
+    }
+
+    //fee on what the venues pay out, rounded up
+    pub fn fee_on(&self, received_amount: TokenAmount) -> Result<TokenAmount, SwapError> {
+
+        let units = received_amount.units.checked_mul(self.fee_bps as u128).ok_or(SwapError::AmountOverflow)?.div_ceil(10_000);
+        Ok(TokenAmount::from_units(units, received_amount.decimals))
+    }
+
+    //Source a swap from the venues on the aggregator's network. Prices are after its fee,
+    //so the slippage still only measures the pools.
+    pub fn quote(&self, venues: &[Box<dyn Venue>], gas: &GasModel, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<AggregatorQuote, SwapError> {
+
+        let legs = find_best_split_with_route(venues, gas, self.network, from, to, amount)?;
+        let quote = order_quote(&legs)?;
+
+        let fee = self.fee_on(quote.received_amount)?;
+        let received_amount = quote.received_amount.checked_sub(fee)?;
+        let fee_factor = 1.0 - self.fee_bps as f64 / 10_000.0;
+
+        Ok(AggregatorQuote {
+            gas_units: order_gas_units(&legs) + self.gas_units,
+            legs,
+            quote: SwapQuote {
+                received_amount,
+                spot_price: quote.spot_price * fee_factor,
+                execution_price: received_amount.to_f64() / amount.to_f64(),
+                price_after: quote.price_after * fee_factor,
+                lp_fee: quote.lp_fee,
+                protocol_fee: quote.protocol_fee,
+            },
+            fee,
+        })
+    }
+
+    //book the fee taken from an executed swap
+    pub fn collect_fee(&mut self, token: &TokenType, fee: TokenAmount) -> Result<(), SwapError> {
+
+        let collected = self.collected_fees.entry(token.clone()).or_insert(TokenAmount::zero(fee.decimals));
+        *collected = collected.checked_add(fee)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+    use crate::router::find_best_split;
+    use crate::swap::{Swap, perform_swap};
+    use crate::token::Token;
+    use crate::user::{User, UserDatabase};
+
+    // A shallow USDC -> USDT pool on Uniswap, and a deep way round through DAI on Sushi
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "DAI"
+        name = "Dai"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "100000"
+        to_reserve = "100000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Sushi"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "DAI"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "DAI"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    #[test]
+    fn split_with_route_shares_the_order_with_a_route_through_other_tokens() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+        let amount = TokenAmount::whole(50_000, 6).unwrap();
+
+        let direct = find_best_split(&venues, &config.gas, Network::Ethereum, &usdc, &usdt, amount).unwrap();
+        let legs = find_best_split_with_route(&venues, &config.gas, Network::Ethereum, &usdc, &usdt, amount).unwrap();
+
+        // the direct pool and the way through DAI both take part, and the order is all sent
+        let hops: Vec<usize> = legs.iter().map(|leg| leg.route.len()).collect();
+        assert_eq!(hops, [1, 2]);
+        assert_eq!(legs[1].route[0].to_token, TokenType::new("DAI"));
+        assert_eq!(legs[0].amount_in.checked_add(legs[1].amount_in).unwrap(), amount);
+
+        assert!(order_quote(&legs).unwrap().received_amount > order_quote(&direct).unwrap().received_amount);
+    }
+
+    #[test]
+    fn fee_comes_off_what_the_venues_pay() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+        let amount = TokenAmount::whole(1_000, 6).unwrap();
+
+        let aggregator = Aggregator::new(Exchange::Matcha, Network::Ethereum, 30, 40_000);
+        let quoted = aggregator.quote(&venues, &config.gas, &usdc, &usdt, amount).unwrap();
+        let venues_pay = order_quote(&quoted.legs).unwrap().received_amount;
+
+        assert_eq!(quoted.fee, aggregator.fee_on(venues_pay).unwrap());
+        assert_eq!(quoted.quote.received_amount, venues_pay.checked_sub(quoted.fee).unwrap());
+        assert_eq!(quoted.gas_units, order_gas_units(&quoted.legs) + 40_000);
+    }
+
+    #[test]
+    fn executed_swap_pays_the_fee_to_the_aggregator() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+        let mut aggregators = [Aggregator::new(Exchange::Matcha, Network::Ethereum, 30, 40_000)];
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+
+        let mut user_db = UserDatabase::new_db();
+        user_db.add_user(User::new(Network::Ethereum, "0xtaker".to_string(), &config.tokens));
+        user_db.users[0].add_balance(Network::Ethereum, &usdc, TokenAmount::whole(50_000, 6).unwrap()).unwrap();
+        let usdt_before = user_db.users[0].balance(Network::Ethereum, &usdt).unwrap();
+
+        // neither the one pool nor the way through DAI alone beats splitting between them
+        let swap = Swap {
+            user_wallet_address: "0xtaker".to_string(),
+            from_token: Token { token_type: usdc },
+            to_token: Token { token_type: usdt.clone() },
+            amount: TokenAmount::whole(50_000, 6).unwrap(),
+            max_slippage: 1.0,
+        };
+        let result = perform_swap(&swap, &mut venues, &mut aggregators, &config.gas, &mut user_db).unwrap();
+        assert_eq!(result.aggregator.as_deref(), Some("Matcha"));
+
+        // the fee is the last one booked, the user is paid what's left after it
+        let fee = result.fees.last().unwrap();
+        let venues_paid = result.received_amount.checked_add(fee.protocol_fee).unwrap();
+        assert_eq!(fee.venue_name, "Matcha");
+        assert_eq!(fee.protocol_fee, aggregators[0].fee_on(venues_paid).unwrap());
+        assert_eq!(aggregators[0].collected_fees[&usdt], fee.protocol_fee);
+        assert_eq!(user_db.users[0].balance(Network::Ethereum, &usdt).unwrap(), usdt_before.checked_add(result.received_amount).unwrap());
+    }
+
+    #[test]
+    fn fee_is_rounded_up_and_collected_per_token() {
+
+        let mut aggregator = Aggregator::new(Exchange::Matcha, Network::Ethereum, 30, 40_000);
+        let usdt = TokenType::new("USDT");
+
+        assert_eq!(aggregator.fee_on(TokenAmount::from_units(10_000, 6)).unwrap().units, 30);
+        assert_eq!(aggregator.fee_on(TokenAmount::from_units(10_001, 6)).unwrap().units, 31);
+        assert_eq!(aggregator.fee_on(TokenAmount::from_units(1, 6)).unwrap().units, 1);
+        assert!(aggregator.fee_on(TokenAmount::zero(6)).unwrap().is_zero());
+        assert!(matches!(aggregator.fee_on(TokenAmount::from_units(u128::MAX, 18)), Err(SwapError::AmountOverflow)));
+
+        aggregator.collect_fee(&usdt, TokenAmount::from_units(30, 6)).unwrap();
+        aggregator.collect_fee(&usdt, TokenAmount::from_units(31, 6)).unwrap();
+        assert_eq!(aggregator.collected_fees[&usdt].units, 61);
+    }
+}
@@ -0,0 +1,597 @@
+use crate::error::SwapError;
+use crate::exchange::{Exchange, Network};
+use crate::gas::GasModel;
+use crate::router::{Router, RouteHop, net_units, route_quote};
+use crate::token::{TokenAmount, TokenType, mul_div};
+use crate::user::{User, UserDatabase};
+use crate::venue::{StagedVenues, Venue};
+
+//an order waiting for its batch to close, amount_in is held in escrow until then
+#[derive(Debug, Clone)]
+pub struct BatchOrder {
+
+    pub id: u64,
+    pub wallet_address: String,
+    pub from_token: TokenType,
+    pub to_token: TokenType,
+    pub amount_in: TokenAmount,
+    pub min_received: TokenAmount,   //limit, the order is refunded if the batch can't pay this
+}
+
+//how an order settled once its batch closed
+#[derive(Debug, Clone)]
+pub struct BatchFill {
+
+    pub order: BatchOrder,
+    pub matched_amount: TokenAmount,          //part of amount_in matched with opposing orders, in from_token
+    pub received_amount: TokenAmount,         //in to_token after gas, zero if refunded
+    pub clearing_price: f64,                  //to_token per from_token the matched part traded at, 0 if nothing matched
+    pub direct_amount: Option<TokenAmount>,   //what the order alone would have got from the AMMs after gas
+    pub refunded: bool,                       //the limit couldn't be met, amount_in went back to the user
+}
+
+//CowSwap style batch auction on one network. Orders are collected until the batch window
+//closes, then opposing orders are matched with each other at a uniform clearing price and
+//only what's left over is routed through the AMMs.
+pub struct BatchAuction {
+
+    pub exchange_name: Exchange,
+    pub network: Network,
+    pub window_blocks: u64,      //blocks a batch stays open after its first order
+    orders: Vec<BatchOrder>,
+    closes_at: Option<u64>,      //block the open batch settles at, None when there's no batch
+    next_order_id: u64,
+}
+
+impl BatchAuction {
+
+    pub fn new(exchange_name: Exchange, network: Network, window_blocks: u64) -> Self {
+
+        BatchAuction { exchange_name, network, window_blocks, orders: Vec::new(), closes_at: None, next_order_id: 0 }
+    }
+
+    pub fn orders(&self) -> &[BatchOrder] {
+
+        &self.orders
+    }
+
+    pub fn closes_at(&self) -> Option<u64> {
+
+        self.closes_at
+    }
+
+    //Add an order to the open batch, or open one. The amount leaves the user's balance
+    //straight away and is held until the batch settles.
+    pub fn place_order(&mut self, user: &mut User, from: &TokenType, to: &TokenType, amount_in: TokenAmount, min_received: TokenAmount, current_block: u64) -> Result<BatchOrder, SwapError> {
+
+        if user.network != self.network {
+            return Err(SwapError::IncorrectNetwork);
+        }
+
+        if amount_in.is_zero() || from == to {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        user.deduct_balance(self.network, from, amount_in)?;
+
+        let order = BatchOrder {
+            id: self.next_order_id,
+            wallet_address: user.wallet_address.clone(),
+            from_token: from.clone(),
+            to_token: to.clone(),
+            amount_in,
+            min_received,
+        };
+
+        self.next_order_id += 1;
+        self.orders.push(order.clone());
+        self.closes_at.get_or_insert(current_block.saturating_add(self.window_blocks));
+
+        Ok(order)
+    }
+
+    //Settle the batch if its window has closed by `current_block`, and credit every order's
+    //owner. Nothing happens while the batch is still open. The batch is cleared on copies of
+    //the venues and paid out on copies of the users, which only replace them once every pair
+    //went through; if anything fails nothing has moved and the orders stay in the batch.
+    pub fn settle(&mut self, current_block: u64, venues: &mut [Box<dyn Venue>], gas: &GasModel, user_db: &mut UserDatabase) -> Result<Vec<BatchFill>, SwapError> {
+
+        match self.closes_at {
+            Some(block) if block <= current_block => {},
+            _ => return Ok(Vec::new()),
+        }
+
+        let orders = &self.orders;
+        let mut settled_venues: Vec<Box<dyn Venue>> = venues.iter().map(|venue| venue.clone_venue()).collect();
+
+        // each token pair clears on its own, with the orders going both ways
+        let mut pairs: Vec<(TokenType, TokenType)> = orders.iter().map(|order| token_pair(&order.from_token, &order.to_token)).collect();
+        pairs.sort();
+        pairs.dedup();
+
+        let mut fills = Vec::new();
+
+        for pair in pairs {
+            let pair_orders = orders.iter()
+                .filter(|order| token_pair(&order.from_token, &order.to_token) == pair)
+                .cloned()
+                .collect();
+
+            fills.extend(self.clear_pair(pair_orders, &pair.0, &pair.1, &mut settled_venues, gas)?);
+        }
+
+        let mut settled_users = user_db.users.clone();
+
+        for fill in &fills {
+            let user = settled_users.iter_mut()
+                .find(|user| user.wallet_address == fill.order.wallet_address)
+                .ok_or(SwapError::UserNotFound)?;
+
+            if fill.refunded {
+                user.add_balance(self.network, &fill.order.from_token, fill.order.amount_in)?;
+            } else {
+                user.add_balance(self.network, &fill.order.to_token, fill.received_amount)?;
+            }
+        }
+
+        for (venue, settled) in venues.iter_mut().zip(settled_venues) {
+            *venue = settled;
+        }
+
+        user_db.users = settled_users;
+        self.orders.clear();
+        self.closes_at = None;
+
+        Ok(fills)
+    }
+
+    //Clear the orders between two tokens. The side selling less is matched in full against
+    //the other at the clearing price, the rest of the other side goes through the AMMs and
+    //its output is shared pro-rata. Orders whose limit isn't met are refunded and the pair
+    //is cleared again without them.
+    fn clear_pair(&self, orders: Vec<BatchOrder>, a: &TokenType, b: &TokenType, venues: &mut [Box<dyn Venue>], gas: &GasModel) -> Result<Vec<BatchFill>, SwapError> {
+
+        // what every order would get on its own, for comparison
+        let direct: Vec<Option<TokenAmount>> = orders.iter()
+            .map(|order| {
+                Router::find_best_route(venues, gas, self.network, &order.from_token, &order.to_token, order.amount_in).ok()
+                    .map(|route| TokenAmount::from_units(route_net_units(gas, self.network, &route, &order.to_token), order.min_received.decimals))
+            })
+            .collect();
+
+        // every order of the pair is in a or b, so one of them has the decimals of each token
+        let decimals = |token: &TokenType| orders.iter()
+            .find_map(|order| if &order.from_token == token { Some(order.amount_in.decimals) } else { None })
+            .unwrap_or_else(|| orders.iter().find(|order| &order.to_token == token).unwrap().min_received.decimals);
+        let (a_decimals, b_decimals) = (decimals(a), decimals(b));
+
+        let mut active: Vec<usize> = (0..orders.len()).collect();
+
+        loop {
+
+            let sells_a: Vec<usize> = active.iter().copied().filter(|&index| &orders[index].from_token == a).collect();
+            let sells_b: Vec<usize> = active.iter().copied().filter(|&index| &orders[index].from_token == b).collect();
+
+            let total = |side: &[usize]| side.iter().map(|&index| orders[index].amount_in.units).sum::<u128>();
+            let (total_a, total_b) = (total(&sells_a), total(&sells_b));
+
+            // the matched part of each side, in base units of the token it sells
+            let price = match (total_a, total_b) {
+                (0, _) | (_, 0) => None,
+                _ => self.clearing_price(venues, (a, a_decimals), (b, b_decimals)),
+            };
+
+            let (matched_a, matched_b) = match price {
+                Some(price) if total_a as f64 * price <= total_b as f64 => (total_a, (total_a as f64 * price).floor() as u128),
+                Some(price) => (((total_b as f64 / price).floor() as u128).min(total_a), total_b),
+                None => (0, 0),
+            };
+
+            // whatever isn't matched goes through the AMMs, quoted before anything moves
+            let route_a = self.residual_route(venues, gas, (a, a_decimals), b, total_a - matched_a);
+            let route_b = self.residual_route(venues, gas, (b, b_decimals), a, total_b - matched_b);
+
+            let fill_side = |side: &[usize], total: u128, matched: u128, received_matched: u128, residual: u128| -> Result<Vec<(usize, u128, u128)>, SwapError> {
+
+                side.iter()
+                    .map(|&index| {
+                        let amount = orders[index].amount_in.units;
+                        let received = mul_div(amount, received_matched, total)? + mul_div(amount, residual, total)?;
+
+                        Ok((index, mul_div(amount, matched, total)?, received))
+                    })
+                    .collect()
+            };
+
+            // each side gets its matched part at the clearing price and a pro-rata share of
+            // what its residual pays out after gas
+            let plan = |residual_a: u128, residual_b: u128| -> Result<Vec<(usize, u128, u128)>, SwapError> {
+
+                let mut planned = fill_side(&sells_a, total_a.max(1), matched_a, matched_b, residual_a)?;
+                planned.extend(fill_side(&sells_b, total_b.max(1), matched_b, matched_a, residual_b)?);
+
+                Ok(planned)
+            };
+
+            let quoted_out = |route: &Option<Result<Vec<RouteHop>, SwapError>>, to: &TokenType| match route {
+                Some(Ok(route)) => route_net_units(gas, self.network, route, to),
+                _ => 0,
+            };
+
+            let planned = plan(quoted_out(&route_a, b), quoted_out(&route_b, a))?;
+
+            // orders the batch can't pay their limit drop out, and so does a side whose
+            // residual can't be routed at all. The rest clear again without them.
+            let mut dropped: Vec<usize> = planned.iter()
+                .filter(|(index, _, received)| *received < orders[*index].min_received.units)
+                .map(|(index, _, _)| *index)
+                .collect();
+
+            if let Some(Err(_)) = route_a {
+                dropped.extend(&sells_a);
+            }
+
+            if let Some(Err(_)) = route_b {
+                dropped.extend(&sells_b);
+            }
+
+            if !dropped.is_empty() {
+                active.retain(|index| !dropped.contains(index));
+                continue;
+            }
+
+            // Pay out what the routes actually returned, in case both went through a shared pool.
+            // They run on copies of the pools, kept only if every order still gets its limit.
+            let mut staged = StagedVenues::new();
+
+            let mut executed_out = |route: &Option<Result<Vec<RouteHop>, SwapError>>, to: &TokenType| -> Result<u128, SwapError> {
+
+                match route {
+                    Some(Ok(route)) => {
+                        let received_amount = execute_route(&mut staged, venues, route)?;
+                        let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+
+                        Ok(net_units(gas, self.network, gas_units, to, received_amount))
+                    },
+                    _ => Ok(0),
+                }
+            };
+
+            let residual_a = executed_out(&route_a, b)?;
+            let residual_b = executed_out(&route_b, a)?;
+            let planned = plan(residual_a, residual_b)?;
+
+            let short: Vec<usize> = planned.iter()
+                .filter(|(index, _, received)| *received < orders[*index].min_received.units)
+                .map(|(index, _, _)| *index)
+                .collect();
+
+            if !short.is_empty() {
+                active.retain(|index| !short.contains(index));
+                continue;
+            }
+
+            staged.commit(venues);
+
+            let mut fills: Vec<BatchFill> = planned.into_iter()
+                .map(|(index, matched, received)| {
+                    let order = &orders[index];
+                    let clearing_price = match price {
+                        Some(price) if matched > 0 => {
+                            let scale = 10f64.powi(order.amount_in.decimals as i32 - order.min_received.decimals as i32);
+                            let price = if &order.from_token == a { price } else { 1.0 / price };
+
+                            price * scale
+                        },
+                        _ => 0.0,
+                    };
+
+                    BatchFill {
+                        order: order.clone(),
+                        matched_amount: TokenAmount::from_units(matched, order.amount_in.decimals),
+                        received_amount: TokenAmount::from_units(received, order.min_received.decimals),
+                        clearing_price,
+                        direct_amount: direct[index],
+                        refunded: false,
+                    }
+                })
+                .collect();
+
+            fills.extend((0..orders.len()).filter(|index| !active.contains(index)).map(|index| BatchFill {
+                order: orders[index].clone(),
+                matched_amount: TokenAmount::zero(orders[index].amount_in.decimals),
+                received_amount: TokenAmount::zero(orders[index].min_received.decimals),
+                clearing_price: 0.0,
+                direct_amount: direct[index],
+                refunded: true,
+            }));
+
+            fills.sort_by_key(|fill| fill.order.id);
+            return Ok(fills);
+        }
+    }
+
+    //Route for what's left of one side after matching, None when nothing is left
+    fn residual_route(&self, venues: &[Box<dyn Venue>], gas: &GasModel, (from, decimals): (&TokenType, u8), to: &TokenType, amount: u128) -> Option<Result<Vec<RouteHop>, SwapError>> {
+
+        match amount {
+            0 => None,
+            amount => Some(Router::find_best_route(venues, gas, self.network, from, to, TokenAmount::from_units(amount, decimals))),
+        }
+    }
+
+    //Uniform price both sides of a pair trade at, in base units of b per base unit of a.
+    //The best AMM spot prices either way are after fees, so their geometric mean cancels
+    //the fees out and lands in the middle of the spread. None when there's no price, or
+    //when the AMMs already pay more than that both ways and matching would only lose.
+    fn clearing_price(&self, venues: &[Box<dyn Venue>], a: (&TokenType, u8), b: (&TokenType, u8)) -> Option<f64> {
+
+        // best spot price of one whole token through any route, in base units. Gas is left
+        // out, it would only make a one token route look worthless.
+        let best_spot = |(from, from_decimals): (&TokenType, u8), (to, to_decimals): (&TokenType, u8)| -> Option<f64> {
+
+            TokenAmount::whole(1, from_decimals).ok()
+                .and_then(|one| Router::find_best_route(venues, &GasModel::default(), self.network, from, to, one).ok())
+                .and_then(|route| route_quote(&route).ok())
+                .map(|quote| quote.spot_price * 10f64.powi(to_decimals as i32 - from_decimals as i32))
+        };
+
+        match (best_spot(a, b), best_spot(b, a)) {
+            (Some(a_to_b), Some(b_to_a)) if a_to_b * b_to_a < 1.0 => Some((a_to_b / b_to_a).sqrt()),
+            (Some(_), Some(_)) => None,
+            (Some(a_to_b), None) => Some(a_to_b),
+            (None, Some(b_to_a)) => Some(1.0 / b_to_a),
+            (None, None) => None,
+        }
+    }
+}
+
+//the two tokens of an order in a fixed order, so both directions land on the same pair
+fn token_pair(from: &TokenType, to: &TokenType) -> (TokenType, TokenType) {
+
+    if from < to {
+        (from.clone(), to.clone())
+    } else {
+        (to.clone(), from.clone())
+    }
+}
+
+//what a route pays out in base units once its gas is paid
+fn route_net_units(gas: &GasModel, network: Network, route: &[RouteHop], to: &TokenType) -> u128 {
+
+    let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+    net_units(gas, network, gas_units, to, route.last().unwrap().quote.received_amount)
+}
+
+//send an amount down a route on staged copies of its venues, each hop passing on what the
+//previous one paid out
+fn execute_route(staged: &mut StagedVenues, venues: &[Box<dyn Venue>], route: &[RouteHop]) -> Result<TokenAmount, SwapError> {
+
+    let mut amount = route.first().ok_or(SwapError::InvalidAmount)?.amount_in;
+
+    for hop in route {
+        amount = staged.execute(venues, hop.venue_index, &hop.from_token, &hop.to_token, amount)?.received_amount;
+    }
+
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::{Config, parse_toml};
+    use crate::exchange::ExchangeData;
+    use crate::pricing::SwapQuote;
+
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "USDT"
+        to = "USDC"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[batch_auctions]]
+        name = "CowSwap"
+        network = "Ethereum"
+        window_blocks = 5
+    "#;
+
+    //quotes USDC -> USDT at par but only pays out half of it
+    #[derive(Clone)]
+    struct ShortVenue;
+
+    impl Venue for ShortVenue {
+
+        fn name(&self) -> &str {
+
+            "Short"
+        }
+
+        fn network(&self) -> Network {
+
+            Network::Ethereum
+        }
+
+        fn supported_pairs(&self) -> Vec<(TokenType, TokenType)> {
+
+            vec![(usdc(), usdt())]
+        }
+
+        fn quote(&self, _from: &TokenType, _to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            Ok(SwapQuote {
+                received_amount: amount_in,
+                spot_price: 1.0,
+                execution_price: 1.0,
+                price_after: 1.0,
+                lp_fee: TokenAmount::zero(amount_in.decimals),
+                protocol_fee: TokenAmount::zero(amount_in.decimals),
+            })
+        }
+
+        fn execute(&mut self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            let quote = self.quote(from, to, amount_in)?;
+            Ok(SwapQuote { received_amount: TokenAmount::from_units(amount_in.units / 2, amount_in.decimals), ..quote })
+        }
+    }
+
+    fn usdc() -> TokenType {
+
+        TokenType::new("USDC")
+    }
+
+    fn usdt() -> TokenType {
+
+        TokenType::new("USDT")
+    }
+
+    fn amount(whole: u64) -> TokenAmount {
+
+        TokenAmount::whole(whole, 6).unwrap()
+    }
+
+    //the config's pools and auction, and two users with 10000 of each token
+    fn setup() -> (Config, Vec<Box<dyn Venue>>, BatchAuction, UserDatabase) {
+
+        let mut config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(std::mem::take(&mut config.exchanges));
+        let auction = config.auctions.remove(0);
+        let mut user_db = UserDatabase::new_db();
+
+        for wallet_address in ["0xa", "0xb"] {
+            let mut user = User::new(Network::Ethereum, wallet_address.to_string(), &config.tokens);
+            user.balances.insert(Network::Ethereum, [(usdc(), amount(10_000)), (usdt(), amount(10_000))].into());
+            user_db.add_user(user);
+        }
+
+        (config, venues, auction, user_db)
+    }
+
+    fn balance(user_db: &mut UserDatabase, wallet_address: &str, token: &TokenType) -> TokenAmount {
+
+        user_db.get_user_by_address(wallet_address).unwrap().balance(Network::Ethereum, token).unwrap()
+    }
+
+    fn pool_quote(venues: &[Box<dyn Venue>]) -> TokenAmount {
+
+        venues[0].quote(&usdc(), &usdt(), amount(1_000)).unwrap().received_amount
+    }
+
+    #[test]
+    fn opposing_orders_match_once_the_window_closes() {
+
+        let (config, mut venues, mut auction, mut user_db) = setup();
+
+        let user = user_db.get_user_by_address_mut("0xa").unwrap();
+        auction.place_order(user, &usdc(), &usdt(), amount(1_000), amount(0), 0).unwrap();
+        let user = user_db.get_user_by_address_mut("0xb").unwrap();
+        auction.place_order(user, &usdt(), &usdc(), amount(400), amount(0), 2).unwrap();
+
+        // escrowed straight away, the window runs from the first order
+        assert_eq!(balance(&mut user_db, "0xa", &usdc()), amount(9_000));
+        assert_eq!(balance(&mut user_db, "0xb", &usdt()), amount(9_600));
+        assert_eq!(auction.closes_at(), Some(5));
+        assert!(auction.settle(4, &mut venues, &config.gas, &mut user_db).unwrap().is_empty());
+
+        let fills = auction.settle(5, &mut venues, &config.gas, &mut user_db).unwrap();
+
+        assert_eq!(fills.len(), 2);
+        assert!(fills.iter().all(|fill| !fill.refunded && fill.clearing_price > 0.0));
+
+        // the smaller side is matched in full, the other only for what it's worth
+        assert_eq!(fills[1].matched_amount, amount(400));
+        assert!(fills[0].matched_amount > amount(390) && fills[0].matched_amount < amount(410));
+
+        assert_eq!(balance(&mut user_db, "0xa", &usdt()), amount(10_000).checked_add(fills[0].received_amount).unwrap());
+        assert_eq!(balance(&mut user_db, "0xb", &usdc()), amount(10_000).checked_add(fills[1].received_amount).unwrap());
+        assert!(auction.orders().is_empty());
+        assert_eq!(auction.closes_at(), None);
+    }
+
+    #[test]
+    fn order_short_of_its_limit_is_refunded() {
+
+        let (config, mut venues, mut auction, mut user_db) = setup();
+        let pool_before = pool_quote(&venues);
+
+        let user = user_db.get_user_by_address_mut("0xa").unwrap();
+        auction.place_order(user, &usdc(), &usdt(), amount(1_000), amount(1_000), 0).unwrap();
+
+        let fills = auction.settle(5, &mut venues, &config.gas, &mut user_db).unwrap();
+
+        assert!(fills[0].refunded);
+        assert_eq!(balance(&mut user_db, "0xa", &usdc()), amount(10_000));
+        assert_eq!(balance(&mut user_db, "0xa", &usdt()), amount(10_000));
+        assert_eq!(pool_quote(&venues), pool_before);
+    }
+
+    #[test]
+    fn limit_is_checked_against_what_was_executed() {
+
+        let (config, _, mut auction, mut user_db) = setup();
+        let mut venues: Vec<Box<dyn Venue>> = vec![Box::new(ShortVenue)];
+
+        // the quote pays 1000, the execution only 500
+        let user = user_db.get_user_by_address_mut("0xa").unwrap();
+        auction.place_order(user, &usdc(), &usdt(), amount(1_000), amount(900), 0).unwrap();
+
+        let fills = auction.settle(5, &mut venues, &config.gas, &mut user_db).unwrap();
+
+        assert!(fills[0].refunded);
+        assert_eq!(balance(&mut user_db, "0xa", &usdc()), amount(10_000));
+        assert_eq!(balance(&mut user_db, "0xa", &usdt()), amount(10_000));
+    }
+
+    #[test]
+    fn failed_settlement_keeps_the_orders_and_changes_nothing() {
+
+        let (config, mut venues, mut auction, mut user_db) = setup();
+        let pool_before = pool_quote(&venues);
+
+        let user = user_db.get_user_by_address_mut("0xa").unwrap();
+        auction.place_order(user, &usdc(), &usdt(), amount(1_000), amount(0), 0).unwrap();
+        let user = user_db.get_user_by_address_mut("0xb").unwrap();
+        auction.place_order(user, &usdc(), &usdt(), amount(1_000), amount(0), 0).unwrap();
+
+        // 0xb's order goes through the pool, then 0xa can't be paid
+        user_db.remove_user_by_address("0xa");
+
+        let result = auction.settle(5, &mut venues, &config.gas, &mut user_db);
+
+        assert!(matches!(result, Err(SwapError::UserNotFound)));
+        assert_eq!(auction.orders().len(), 2);
+        assert_eq!(auction.closes_at(), Some(5));
+        assert_eq!(pool_quote(&venues), pool_before);
+        assert_eq!(balance(&mut user_db, "0xb", &usdt()), amount(10_000));
+    }
+}
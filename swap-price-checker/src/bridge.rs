@@ -0,0 +1,293 @@
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::token::{TokenAmount, TokenType};
+use crate::user::User;
+
+//one direction of a bridge for one token, e.g. USDC from Ethereum to Polygon
+#[derive(Debug, Clone)]
+pub struct BridgeRoute {
+
+    pub token: TokenType,
+    pub from_network: Network,
+    pub to_network: Network,
+    pub to_decimals: u8,          //decimals of the token on the destination network
+    pub fee_bps: u32,             //taken from the amount sent, in basis points
+    pub min_amount: TokenAmount,  //smallest amount the bridge accepts, in the source decimals
+    pub delay_blocks: u64,        //blocks until the tokens arrive on the destination network
+}
+
+//what a bridge transfer would cost and deliver, before sending it
+#[derive(Debug, Clone, Copy)]
+pub struct BridgeQuote {
+
+    pub fee: TokenAmount,              //in the source decimals
+    pub received_amount: TokenAmount,  //in the destination decimals
+    pub delay_blocks: u64,
+}
+
+//tokens that left the source network and are still on their way
+#[derive(Debug, Clone)]
+pub struct PendingTransfer {
+
+    pub token: TokenType,
+    pub from_network: Network,
+    pub to_network: Network,
+    pub amount_sent: TokenAmount,
+    pub fee: TokenAmount,
+    pub received_amount: TokenAmount,
+    pub arrival_block: u64,       //the transfer is credited once the bridge reaches this block
+}
+
+//Mock bridge between networks. There is one block clock shared by every network,
+//transfers are credited on the destination once the clock passes their arrival block.
+pub struct Bridge {
+
+    pub routes: Vec<BridgeRoute>,
+    block: u64,
+}
+
+impl BridgeRoute {
+
+    pub fn quote(&self, amount: TokenAmount) -> Result<BridgeQuote, SwapError> {
+
+        if amount.is_zero() {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        if amount < self.min_amount {
+            return Err(SwapError::BelowBridgeMinimum);
+        }
+
+        // the fee is rounded up, the bridge never charges less than its rate
+        let fee_units = amount.units.checked_mul(self.fee_bps as u128).ok_or(SwapError::AmountOverflow)?.div_ceil(10_000);
+        let fee = TokenAmount::from_units(fee_units, amount.decimals);
+
+        Ok(BridgeQuote {
+            fee,
+            received_amount: amount.checked_sub(fee)?.rescale(self.to_decimals)?,
+            delay_blocks: self.delay_blocks,
+        })
+    }
+}
+
+impl Bridge {
+
+    pub fn new(routes: Vec<BridgeRoute>) -> Self {
+
+        Bridge { routes, block: 0 }
+    }
+
+    //a bridge whose clock carries on from a saved block, so saved transfers keep their arrival
+    pub fn starting_at(routes: Vec<BridgeRoute>, block: u64) -> Self {
+
+        Bridge { routes, block }
+    }
+
+    pub fn current_block(&self) -> u64 {
+
+        self.block
+    }
+
+    //move the clock forward, transfers due by then can be settled on the users. It stops at
+    //u64::MAX, where everything is due.
+    pub fn advance_blocks(&mut self, blocks: u64) {
+
+        self.block = self.block.saturating_add(blocks);
+    }
+
+    pub fn route(&self, token: &TokenType, from_network: Network, to_network: Network) -> Result<&BridgeRoute, SwapError> {
+
+        self.routes.iter()
+            .find(|route| &route.token == token && route.from_network == from_network && route.to_network == to_network)
+            .ok_or(SwapError::BridgeRouteNotSupported)
+    }
+
+    //networks a token can be bridged to from a network
+    pub fn destinations(&self, token: &TokenType, from_network: Network) -> Vec<Network> {
+
+        self.routes.iter()
+            .filter(|route| &route.token == token && route.from_network == from_network)
+            .map(|route| route.to_network)
+            .collect()
+    }
+
+    //Send tokens from the user's current network to another one. The amount leaves the
+    //user's balance straight away and shows up as a pending transfer until it arrives.
+    pub fn transfer(&self, user: &mut User, token: &TokenType, to_network: Network, amount: TokenAmount) -> Result<PendingTransfer, SwapError> {
+
+        let from_network = user.network;
+        let route = self.route(token, from_network, to_network)?;
+        let quote = route.quote(amount)?;
+
+        user.deduct_balance(from_network, token, amount)?;
+
+        let transfer = PendingTransfer {
+            token: token.clone(),
+            from_network,
+            to_network,
+            amount_sent: amount,
+            fee: quote.fee,
+            received_amount: quote.received_amount,
+            arrival_block: self.block.saturating_add(route.delay_blocks),
+        };
+
+        user.pending_transfers.push(transfer.clone());
+        Ok(transfer)
+    }
+
+    //Credit every transfer of the user that has arrived by the current block. It's done on a
+    //copy of the user, so if a credit fails every transfer is still pending afterwards.
+    pub fn settle(&self, user: &mut User) -> Result<Vec<PendingTransfer>, SwapError> {
+
+        let mut settled = user.clone();
+
+        let (arrived, pending): (Vec<PendingTransfer>, Vec<PendingTransfer>) = settled.pending_transfers.drain(..)
+            .partition(|transfer| transfer.arrival_block <= self.block);
+
+        settled.pending_transfers = pending;
+
+        for transfer in &arrived {
+            settled.add_balance(transfer.to_network, &transfer.token, transfer.received_amount)?;
+        }
+
+        *user = settled;
+        Ok(arrived)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn usdc() -> TokenType {
+
+        TokenType::new("USDC")
+    }
+
+    fn usdc_amount(amount: &str) -> TokenAmount {
+
+        TokenAmount::parse(amount, 6).unwrap()
+    }
+
+    //USDC from Ethereum to Polygon for 0.1%, at least 10 USDC, arriving 5 blocks later
+    fn bridge() -> Bridge {
+
+        Bridge::new(vec![BridgeRoute {
+            token: usdc(),
+            from_network: Network::Ethereum,
+            to_network: Network::Polygon,
+            to_decimals: 6,
+            fee_bps: 10,
+            min_amount: usdc_amount("10"),
+            delay_blocks: 5,
+        }])
+    }
+
+    fn user(usdc_balance: &str) -> User {
+
+        User {
+            network: Network::Ethereum,
+            wallet_address: "0xbridge".to_string(),
+            balances: HashMap::from([(Network::Ethereum, HashMap::from([(usdc(), usdc_amount(usdc_balance))]))]),
+            pending_transfers: Vec::new(),
+            lp_positions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn quote_rounds_the_fee_up_and_enforces_the_minimum() {
+
+        let route = &bridge().routes[0];
+
+        let quote = route.quote(usdc_amount("1000")).unwrap();
+        assert_eq!(quote.fee, usdc_amount("1"));
+        assert_eq!(quote.received_amount, usdc_amount("999"));
+        assert_eq!(quote.delay_blocks, 5);
+
+        // 0.1% of 10.000001 is 0.0100000001, a unit more than 0.01
+        assert_eq!(route.quote(usdc_amount("10.000001")).unwrap().fee, usdc_amount("0.010001"));
+
+        assert!(matches!(route.quote(usdc_amount("9.999999")), Err(SwapError::BelowBridgeMinimum)));
+        assert!(matches!(route.quote(usdc_amount("0")), Err(SwapError::InvalidAmount)));
+    }
+
+    #[test]
+    fn quote_rescales_to_the_destination_decimals() {
+
+        let route = BridgeRoute { to_decimals: 18, ..bridge().routes[0].clone() };
+
+        assert_eq!(route.quote(usdc_amount("1000")).unwrap().received_amount, TokenAmount::whole(999, 18).unwrap());
+    }
+
+    #[test]
+    fn transfer_arrives_at_its_block() {
+
+        let mut bridge = bridge();
+        let mut user = user("1000");
+
+        bridge.advance_blocks(3);
+        let transfer = bridge.transfer(&mut user, &usdc(), Network::Polygon, usdc_amount("400")).unwrap();
+
+        assert_eq!(transfer.arrival_block, 8);
+        assert_eq!(user.balance(Network::Ethereum, &usdc()), Some(usdc_amount("600")));
+
+        bridge.advance_blocks(4);
+        assert!(bridge.settle(&mut user).unwrap().is_empty());
+        assert_eq!(user.pending_transfers.len(), 1);
+        assert_eq!(user.balance(Network::Polygon, &usdc()), None);
+
+        bridge.advance_blocks(1);
+        assert_eq!(bridge.settle(&mut user).unwrap().len(), 1);
+        assert!(user.pending_transfers.is_empty());
+        assert_eq!(user.balance(Network::Polygon, &usdc()), Some(usdc_amount("399.6")));
+    }
+
+    #[test]
+    fn clock_stops_at_the_last_block() {
+
+        let mut bridge = bridge();
+        let mut user = user("1000");
+
+        bridge.advance_blocks(u64::MAX);
+        bridge.advance_blocks(u64::MAX);
+        assert_eq!(bridge.current_block(), u64::MAX);
+
+        // due straight away rather than wrapping around into the past
+        let transfer = bridge.transfer(&mut user, &usdc(), Network::Polygon, usdc_amount("100")).unwrap();
+        assert_eq!(transfer.arrival_block, u64::MAX);
+        assert_eq!(bridge.settle(&mut user).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transfer_needs_a_route_and_the_balance() {
+
+        let bridge = bridge();
+        let mut user = user("100");
+
+        assert!(matches!(bridge.transfer(&mut user, &usdc(), Network::Polygon, usdc_amount("101")), Err(SwapError::InsufficientBalance)));
+        assert!(matches!(bridge.transfer(&mut user, &usdc(), Network::BNBChain, usdc_amount("50")), Err(SwapError::BridgeRouteNotSupported)));
+        assert!(user.pending_transfers.is_empty());
+        assert_eq!(user.balance(Network::Ethereum, &usdc()), Some(usdc_amount("100")));
+    }
+
+    #[test]
+    fn failed_settle_keeps_every_transfer_pending() {
+
+        let mut bridge = bridge();
+        let mut user = user("1000");
+
+        bridge.transfer(&mut user, &usdc(), Network::Polygon, usdc_amount("100")).unwrap();
+        bridge.transfer(&mut user, &usdc(), Network::Polygon, usdc_amount("100")).unwrap();
+
+        // the second one arrives in units the balance isn't kept in, so it can't be credited
+        user.pending_transfers[1].received_amount = TokenAmount::whole(1, 18).unwrap();
+        bridge.advance_blocks(5);
+
+        assert!(matches!(bridge.settle(&mut user), Err(SwapError::InvalidAmount)));
+        assert_eq!(user.pending_transfers.len(), 2);
+        assert_eq!(user.balance(Network::Polygon, &usdc()), None);
+    }
+}
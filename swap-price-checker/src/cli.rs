@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use swap_price_checker::config::Config;
+use swap_price_checker::{
+    BalanceReport, DEFAULT_SLIPPAGE_TOLERANCE, ExchangeData, Network, OutputFormat, QuoteTable, Report, StoreError, Swap, SwapError, SwapReport, Token,
+    TokenAmount, TokenRegistry, TokenType, User, UserDatabase, compare_quotes, perform_swap, plan_swap,
+};
+
+use crate::{print_legs, print_options, print_quote_table, print_swap_result};
+
+pub const USAGE: &str = "Usage: swap-price-checker [--config <exchanges.toml|exchanges.json>] [--db <users.json>] [<command>]
+
+Without a command the interactive menu starts. Commands:
+  quote --from <token> --to <token> --amount <amount> [--network <network>]
+  compare --from <token> --to <token> --amount <amount> [--network <network>]
+  swap --wallet <address> --from <token> --to <token> --amount <amount> [--slippage <percent>] [--network <network>]
+  balances --wallet <address> [--network <network>]
+
+Every command takes --format table|json|csv, table when left out. swap and balances need a
+wallet from the --db file, created in the menu; --network picks the network for that run only.";
+
+//exit code for a bad command line, the same as a bad `--config`
+pub const EXIT_USAGE: i32 = 2;
+
+//why a subcommand stopped, usage errors print the usage and swap errors map to their own exit code
+enum CliError {
+
+    Usage(String),
+    Swap(SwapError),
+    Store(StoreError),
+}
+
+impl From<SwapError> for CliError {
+
+    fn from(error: SwapError) -> Self {
+
+        CliError::Swap(error)
+    }
+}
+
+impl From<StoreError> for CliError {
+
+    fn from(error: StoreError) -> Self {
+
+        CliError::Store(error)
+    }
+}
+
+//Run a subcommand against the config and return the process exit code. Wallets come from the
+//`--db` file, the subcommands never create one.
+pub fn run(command: &str, args: &[String], config: Config, user_db: &mut UserDatabase) -> i32 {
+
+    let result = match command {
+        "quote" => parse_options(args, &["from", "to", "amount", "network", "format"]).and_then(|options| quote(&options, config)),
+        "compare" => parse_options(args, &["from", "to", "amount", "network", "format"]).and_then(|options| compare(&options, config)),
+        "swap" => parse_options(args, &["wallet", "from", "to", "amount", "slippage", "network", "format"]).and_then(|options| swap(&options, config, user_db)),
+        "balances" => parse_options(args, &["wallet", "network", "format"]).and_then(|options| balances(&options, &config.tokens, user_db)),
+        _ => Err(CliError::Usage(format!("unknown command `{}`", command))),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(CliError::Usage(message)) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            EXIT_USAGE
+        },
+        Err(CliError::Swap(error)) => {
+            eprintln!("Error: {}", error);
+            error.exit_code()
+        },
+        Err(CliError::Store(error)) => {
+            eprintln!("Error: {}", error);
+            1
+        },
+    }
+}
+
+//`--name value` pairs, every name has to be one the command takes
+fn parse_options(args: &[String], allowed: &[&str]) -> Result<HashMap<String, String>, CliError> {
+
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let name = arg.strip_prefix("--")
+            .filter(|name| allowed.contains(name))
+            .ok_or_else(|| CliError::Usage(format!("unexpected argument `{}`", arg)))?;
+        let value = args.next().ok_or_else(|| CliError::Usage(format!("`{}` needs a value", arg)))?;
+
+        options.insert(name.to_string(), value.clone());
+    }
+
+    Ok(options)
+}
+
+fn required<'a>(options: &'a HashMap<String, String>, name: &str) -> Result<&'a str, CliError> {
+
+    options.get(name).map(String::as_str).ok_or_else(|| CliError::Usage(format!("missing `--{}`", name)))
+}
+
+//`--network`, None when it isn't given
+fn network_option(options: &HashMap<String, String>) -> Result<Option<Network>, CliError> {
+
+    options.get("network")
+        .map(|name| Network::from_name(name).ok_or_else(|| CliError::Usage(format!("unknown network `{}`", name))))
+        .transpose()
+}
+
+//`--network`, Ethereum when it isn't given
+fn network(options: &HashMap<String, String>) -> Result<Network, CliError> {
+
+    Ok(network_option(options)?.unwrap_or(Network::Ethereum))
+}
+
+//`--format`, the menu's text when it isn't given
+fn format(options: &HashMap<String, String>) -> Result<OutputFormat, CliError> {
+
+    match options.get("format") {
+        Some(name) => OutputFormat::from_name(name).ok_or_else(|| CliError::Usage(format!("unknown format `{}`", name))),
+        None => Ok(OutputFormat::Table),
+    }
+}
+
+//write a report in the chosen format, `table` prints it the way the menu does
+fn print_report(format: OutputFormat, report: &impl Report, table: impl FnOnce()) {
+
+    match format {
+        OutputFormat::Table => table(),
+        OutputFormat::Json => println!("{}", report.json()),
+        OutputFormat::Csv => print!("{}", report.csv()),
+    }
+}
+
+//the `--wallet` user, it has to be in the db already
+fn wallet<'a>(options: &HashMap<String, String>, user_db: &'a mut UserDatabase) -> Result<&'a mut User, CliError> {
+
+    let wallet_address = required(options, "wallet")?;
+    Ok(user_db.get_user_by_address_mut(wallet_address).ok_or(SwapError::UserNotFound)?)
+}
+
+//the from and to tokens and the amount of a quote or swap, checked against the registry
+fn swap_options(options: &HashMap<String, String>, registry: &TokenRegistry, network: Network) -> Result<(TokenType, TokenType, TokenAmount), CliError> {
+
+    let from = TokenType::new(required(options, "from")?);
+    let to = TokenType::new(required(options, "to")?);
+
+    let amount = TokenAmount::parse(required(options, "amount")?, registry.decimals(&from, network)?)?;
+    registry.decimals(&to, network)?;
+
+    Ok((from, to, amount))
+}
+
+fn quote(options: &HashMap<String, String>, config: Config) -> Result<(), CliError> {
+
+    let network = network(options)?;
+    let format = format(options)?;
+    let (from, to, amount) = swap_options(options, &config.tokens, network)?;
+
+    let venues = ExchangeData::into_venues(config.exchanges);
+    let plan = plan_swap(&venues, &config.aggregators, &config.gas, network, &from, &to, amount)?;
+    let report = SwapReport::from_plan(&plan, &config.aggregators, &config.gas, network, &from, &to, amount);
+
+    print_report(format, &report, || {
+        match &report.aggregator {
+            Some(aggregator) => println!("Quote through {}: {} {} -> {} {} on {:?}", aggregator, amount, from, plan.quote.received_amount, to, network),
+            None => println!("Quote: {} {} -> {} {} on {:?}", amount, from, plan.quote.received_amount, to, network),
+        }
+
+        print_options(&plan.options, &to);
+        print_legs(&plan.legs);
+
+        println!("Spot price: {:.6}, execution price: {:.6}, price impact: {:.2} bps, slippage: {:.4}%",
+            report.spot_price, report.execution_price, report.price_impact_bps, report.slippage * 100.0);
+        println!("Gas: {} units, about ${:.2} ({} {})", report.gas_units, report.gas_cost_usd, report.gas_cost, to);
+    });
+
+    Ok(())
+}
+
+fn compare(options: &HashMap<String, String>, config: Config) -> Result<(), CliError> {
+
+    let network = network(options)?;
+    let format = format(options)?;
+    let (from, to, amount) = swap_options(options, &config.tokens, network)?;
+
+    let venues = ExchangeData::into_venues(config.exchanges);
+    let quotes = compare_quotes(&venues, &config.aggregators, &config.gas, network, &from, &to, amount)?;
+
+    print_report(format, &QuoteTable::new(&quotes, network, &from, &to, amount), || print_quote_table(&quotes));
+
+    Ok(())
+}
+
+fn swap(options: &HashMap<String, String>, mut config: Config, user_db: &mut UserDatabase) -> Result<(), CliError> {
+
+    let format = format(options)?;
+    let user = wallet(options, user_db)?;
+    let home_network = user.network;
+    let network = network_option(options)?.unwrap_or(home_network);
+    let wallet_address = user.wallet_address.clone();
+    let (from, to, amount) = swap_options(options, &config.tokens, network)?;
+
+    let max_slippage = match options.get("slippage") {
+        Some(input) => match input.parse::<f64>() {
+            Ok(val) if (0.0..100.0).contains(&val) => val / 100.0,
+            _ => return Err(CliError::Usage(format!("invalid slippage tolerance `{}`", input))),
+        },
+        None => DEFAULT_SLIPPAGE_TOLERANCE,
+    };
+
+    let swap = Swap {
+        user_wallet_address: wallet_address,
+        from_token: Token { token_type: from },
+        to_token: Token { token_type: to },
+        amount,
+        max_slippage,
+    };
+
+    // the swap runs on `--network`, the wallet is back on its own network before it's saved
+    let mut venues = ExchangeData::into_venues(config.exchanges);
+
+    wallet(options, user_db)?.network = network;
+    let result = perform_swap(&swap, &mut venues, &mut config.aggregators, &config.gas, user_db);
+    wallet(options, user_db)?.network = home_network;
+
+    let result = result?;
+    user_db.save()?;
+
+    let report = SwapReport::from_result(&result, network, &swap.from_token.token_type, &swap.to_token.token_type, amount);
+    print_report(format, &report, || print_swap_result(&result, &swap.to_token.token_type));
+
+    Ok(())
+}
+
+fn balances(options: &HashMap<String, String>, registry: &TokenRegistry, user_db: &mut UserDatabase) -> Result<(), CliError> {
+
+    let format = format(options)?;
+    let user = wallet(options, user_db)?;
+    let report = BalanceReport::new(user, registry, network_option(options)?.unwrap_or(user.network));
+
+    print_report(format, &report, || {
+        for row in &report.balances {
+            println!("{}: {}", row.token, row.amount);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use super::*;
+    use swap_price_checker::config::parse_toml;
+
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Polygon"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    //a users file in the temp directory, removed again when the test is done
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+
+        fn drop(&mut self) {
+
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn args(line: &str) -> Vec<String> {
+
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn run_line(command: &str, line: &str, user_db: &mut UserDatabase) -> i32 {
+
+        run(command, &args(line), parse_toml(CONFIG).unwrap(), user_db)
+    }
+
+    #[test]
+    fn options_are_name_value_pairs() {
+
+        let options = parse_options(&args("--from USDC --amount 1.5"), &["from", "to", "amount"]).ok().unwrap();
+        assert_eq!(options.len(), 2);
+        assert_eq!(options["from"], "USDC");
+        assert_eq!(options["amount"], "1.5");
+
+        // a bare value, an option the command doesn't take and an option without its value
+        for line in ["USDC", "--wallet 0x1", "--from USDC --to"] {
+            assert!(matches!(parse_options(&args(line), &["from", "to"]), Err(CliError::Usage(_))), "{}", line);
+        }
+    }
+
+    #[test]
+    fn bad_command_lines_exit_with_usage() {
+
+        let mut user_db = UserDatabase::new_db();
+
+        assert_eq!(run_line("frobnicate", "", &mut user_db), EXIT_USAGE);
+        assert_eq!(run_line("quote", "--from USDC --amount 1", &mut user_db), EXIT_USAGE);
+        assert_eq!(run_line("quote", "--from USDC --to USDT --amount 1 --wallet 0x1", &mut user_db), EXIT_USAGE);
+        assert_eq!(run_line("quote", "--from USDC --to USDT --amount 1 --network Mars", &mut user_db), EXIT_USAGE);
+        assert_eq!(run_line("quote", "--from USDC --to USDT --amount 1 --format xml", &mut user_db), EXIT_USAGE);
+
+        user_db.add_user(User::new(Network::Ethereum, "0xcli".to_string(), &parse_toml(CONFIG).unwrap().tokens));
+        for slippage in ["-1", "100", "NaN", "lots"] {
+            let line = format!("--wallet 0xcli --from USDC --to USDT --amount 1 --slippage {}", slippage);
+            assert_eq!(run_line("swap", &line, &mut user_db), EXIT_USAGE, "{}", slippage);
+        }
+
+        assert_eq!(run_line("quote", "--from USDC --to USDT --amount 1 --format json", &mut user_db), 0);
+    }
+
+    #[test]
+    fn swap_errors_exit_with_their_own_code() {
+
+        let mut user_db = UserDatabase::new_db();
+
+        let code = run_line("swap", "--wallet 0xnobody --from USDC --to USDT --amount 1", &mut user_db);
+        assert_eq!(code, SwapError::UserNotFound.exit_code());
+
+        // USDT is only registered on Ethereum
+        let code = run_line("quote", "--from USDC --to USDT --amount 1 --network Polygon", &mut user_db);
+        assert_eq!(code, SwapError::TokenNotSupported.exit_code());
+
+        let code = run_line("balances", "--wallet 0xnobody", &mut user_db);
+        assert_eq!(code, SwapError::UserNotFound.exit_code());
+    }
+
+    #[test]
+    fn exit_codes_follow_the_declaration_order() {
+
+        let errors = [
+            SwapError::TokenPairNotSupported, SwapError::NotEnoughLiquidity, SwapError::InsufficientBalance, SwapError::IncorrectNetwork,
+            SwapError::BalanceNotFound, SwapError::UserNotFound, SwapError::SlippageExceeded, SwapError::InvalidAmount,
+            SwapError::AmountOverflow, SwapError::TokenNotSupported, SwapError::DuplicateToken, SwapError::BridgeRouteNotSupported,
+            SwapError::BelowBridgeMinimum, SwapError::LiquidityNotSupported, SwapError::PositionNotFound, SwapError::StrategiesNotSupported,
+            SwapError::StrategyNotFound, SwapError::AmountUnderflow,
+        ];
+
+        // 0 is success, 1 a config or store error and 2 a usage error
+        for (index, error) in errors.iter().enumerate() {
+            assert_eq!(error.exit_code(), 10 + index as i32, "{}", error);
+        }
+    }
+
+    #[test]
+    fn swap_on_another_network_saves_the_home_network() {
+
+        let file = TempFile(std::env::temp_dir().join(format!("swap-price-checker-{}-cli.json", std::process::id())));
+        let config = parse_toml(CONFIG).unwrap();
+        let usdc = TokenType::new("USDC");
+
+        let mut user_db = UserDatabase::open(&file.0, &config.tokens).unwrap();
+        user_db.add_user(User::new(Network::Polygon, "0xtraveller".to_string(), &config.tokens));
+        user_db.users[0].add_balance(Network::Ethereum, &usdc, TokenAmount::whole(100, 6).unwrap()).unwrap();
+        let usdc_before = user_db.users[0].balance(Network::Ethereum, &usdc).unwrap();
+
+        let code = run_line("swap", "--wallet 0xtraveller --from USDC --to USDT --amount 100 --slippage 50 --network Ethereum --format json", &mut user_db);
+        assert_eq!(code, 0);
+
+        // the swap was paid on Ethereum, the wallet was saved on Polygon
+        let reopened = UserDatabase::open(&file.0, &config.tokens).unwrap();
+        let user = &reopened.users[0];
+        assert_eq!(user.network, Network::Polygon);
+        assert_eq!(user.balance(Network::Ethereum, &usdc).unwrap(), usdc_before.checked_sub(TokenAmount::whole(100, 6).unwrap()).unwrap());
+        assert_eq!(user_db.users[0].network, Network::Polygon);
+    }
+}
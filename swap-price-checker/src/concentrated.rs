@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+
+use crate::error::SwapError;
+
+// Uniswap v3 style concentrated liquidity for a one directional pool. Prices are to_token
+// base units per from_token base unit and every tick is 0.01% away from the next one.
+// Swapping from_token in only ever moves the price down, so only that direction is needed.
+
+const TICK_BASE: f64 = 1.0001;
+
+//the tick a price sits in, rounded down
+pub fn tick_at_price(price: f64) -> i32 {
+
+    (price.ln() / TICK_BASE.ln()).floor() as i32
+}
+
+pub fn price_at_tick(tick: i32) -> f64 {
+
+    TICK_BASE.powi(tick)
+}
+
+fn sqrt_price_at_tick(tick: i32) -> f64 {
+
+    TICK_BASE.powf(tick as f64 / 2.0)
+}
+
+//an initialized tick, the edge of at least one range
+#[derive(Debug, Clone, PartialEq)]
+struct Tick {
+
+    liquidity_net: f64,        //added when the price crosses the tick going up, taken off going down
+    fee_growth_outside: f64,   //fee growth on the side of the tick the price isn't on
+    positions: u32,            //ranges starting or ending here, the tick goes once there are none
+}
+
+//liquidity placed between two ticks, by the config or by a user
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangePosition {
+
+    pub id: u64,
+    pub lower_tick: i32,
+    pub upper_tick: i32,
+    pub liquidity: f64,
+    fee_growth_inside_last: f64,
+    fees_owed: f64,            //LP fees earned up to the last update, in from_token base units
+}
+
+//what a range position holds at the current price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeAmounts {
+
+    pub from_amount: f64,
+    pub to_amount: f64,
+    pub fees: f64,             //uncollected LP fees, in from_token
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcentratedPool {
+
+    sqrt_price: f64,
+    tick: i32,                 //the price is in [tick, tick + 1)
+    liquidity: f64,            //liquidity of every range the price is in
+    ticks: BTreeMap<i32, Tick>,
+    fee_growth_global: f64,    //LP fees per unit of liquidity, ever
+    positions: Vec<RangePosition>,
+    next_position_id: u64,
+}
+
+impl ConcentratedPool {
+
+    pub fn new(price: f64) -> Self {
+
+        ConcentratedPool {
+            sqrt_price: price.sqrt(),
+            tick: tick_at_price(price),
+            liquidity: 0.0,
+            ticks: BTreeMap::new(),
+            fee_growth_global: 0.0,
+            positions: Vec::new(),
+            next_position_id: 0,
+        }
+    }
+
+    pub fn price(&self) -> f64 {
+
+        self.sqrt_price * self.sqrt_price
+    }
+
+    //liquidity the next swap trades against
+    pub fn active_liquidity(&self) -> f64 {
+
+        self.liquidity
+    }
+
+    pub fn positions(&self) -> &[RangePosition] {
+
+        &self.positions
+    }
+
+    //tokens a range holds for an amount of liquidity at the current price. Below the range
+    //it's all from_token, above it all to_token.
+    pub fn amounts_for(&self, lower_tick: i32, upper_tick: i32, liquidity: f64) -> (f64, f64) {
+
+        let (lower, upper) = (sqrt_price_at_tick(lower_tick), sqrt_price_at_tick(upper_tick));
+        let sqrt_price = self.sqrt_price.clamp(lower, upper);
+
+        (liquidity * (1.0 / sqrt_price - 1.0 / upper), liquidity * (sqrt_price - lower))
+    }
+
+    //the most liquidity a range can get out of the amounts given
+    pub fn liquidity_for(&self, lower_tick: i32, upper_tick: i32, from_amount: f64, to_amount: f64) -> f64 {
+
+        let (from_per_liquidity, to_per_liquidity) = self.amounts_for(lower_tick, upper_tick, 1.0);
+
+        let from_liquidity = if from_per_liquidity > 0.0 { from_amount / from_per_liquidity } else { f64::INFINITY };
+        let to_liquidity = if to_per_liquidity > 0.0 { to_amount / to_per_liquidity } else { f64::INFINITY };
+
+        from_liquidity.min(to_liquidity)
+    }
+
+    //place liquidity between two ticks, returns the id of the new position
+    pub fn add_position(&mut self, lower_tick: i32, upper_tick: i32, liquidity: f64) -> Result<u64, SwapError> {
+
+        if lower_tick >= upper_tick || !liquidity.is_finite() || liquidity <= 0.0 {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        self.update_tick(lower_tick, liquidity, 1);
+        self.update_tick(upper_tick, -liquidity, 1);
+
+        if self.tick >= lower_tick && self.tick < upper_tick {
+            self.liquidity += liquidity;
+        }
+
+        let id = self.next_position_id;
+        self.next_position_id += 1;
+
+        self.positions.push(RangePosition {
+            id,
+            lower_tick,
+            upper_tick,
+            liquidity,
+            fee_growth_inside_last: self.fee_growth_inside(lower_tick, upper_tick),
+            fees_owed: 0.0,
+        });
+
+        Ok(id)
+    }
+
+    //Take liquidity out of a position, with its share of the fees. The position goes once
+    //all of it is out.
+    pub fn remove_position(&mut self, id: u64, liquidity: f64) -> Result<RangeAmounts, SwapError> {
+
+        let index = self.positions.iter().position(|position| position.id == id).ok_or(SwapError::PositionNotFound)?;
+        let position = self.positions[index].clone();
+
+        if !liquidity.is_finite() || liquidity <= 0.0 {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        if liquidity > position.liquidity {
+            return Err(SwapError::InsufficientBalance);
+        }
+
+        let fees_owed = position.fees_owed
+            + position.liquidity * (self.fee_growth_inside(position.lower_tick, position.upper_tick) - position.fee_growth_inside_last);
+        let (from_amount, to_amount) = self.amounts_for(position.lower_tick, position.upper_tick, liquidity);
+        let fees = fees_owed * liquidity / position.liquidity;
+
+        let closed = liquidity == position.liquidity;
+        let count = if closed { -1 } else { 0 };
+
+        self.update_tick(position.lower_tick, -liquidity, count);
+        self.update_tick(position.upper_tick, liquidity, count);
+
+        if self.tick >= position.lower_tick && self.tick < position.upper_tick {
+            self.liquidity = (self.liquidity - liquidity).max(0.0);
+        }
+
+        if closed {
+            self.positions.remove(index);
+        } else {
+            let fee_growth_inside = self.fee_growth_inside(position.lower_tick, position.upper_tick);
+            let stored = &mut self.positions[index];
+
+            stored.liquidity -= liquidity;
+            stored.fees_owed = fees_owed - fees;
+            stored.fee_growth_inside_last = fee_growth_inside;
+        }
+
+        Ok(RangeAmounts { from_amount, to_amount, fees })
+    }
+
+    //what a position could be withdrawn for right now
+    pub fn position_amounts(&self, id: u64) -> Result<RangeAmounts, SwapError> {
+
+        let position = self.positions.iter().find(|position| position.id == id).ok_or(SwapError::PositionNotFound)?;
+        let (from_amount, to_amount) = self.amounts_for(position.lower_tick, position.upper_tick, position.liquidity);
+
+        Ok(RangeAmounts {
+            from_amount,
+            to_amount,
+            fees: position.fees_owed
+                + position.liquidity * (self.fee_growth_inside(position.lower_tick, position.upper_tick) - position.fee_growth_inside_last),
+        })
+    }
+
+    //Swap from_token in and return the to_token paid out. The price walks down through the
+    //ranges, crossing a tick switches the liquidity of the ranges starting or ending there.
+    //`fee` is the LP fee that already came off amount_in, it's shared by whichever liquidity
+    //the swap goes through, in proportion to how much of it went through each range.
+    pub fn swap(&mut self, amount_in: f64, fee: f64) -> Result<f64, SwapError> {
+
+        if amount_in <= 0.0 {
+            return Ok(0.0);
+        }
+
+        let mut remaining = amount_in;
+        let mut amount_out = 0.0;
+
+        while remaining > 0.0 {
+
+            // with nothing left below the price there's no liquidity to swap against
+            let next_tick = *self.ticks.range(..=self.tick).next_back().ok_or(SwapError::NotEnoughLiquidity)?.0;
+            let next_sqrt_price = sqrt_price_at_tick(next_tick);
+
+            if self.liquidity > 0.0 {
+
+                let to_next_tick = self.liquidity * (1.0 / next_sqrt_price - 1.0 / self.sqrt_price);
+
+                // the swap ends inside this range
+                if remaining < to_next_tick {
+
+                    let sqrt_price = self.liquidity * self.sqrt_price / (self.liquidity + remaining * self.sqrt_price);
+
+                    amount_out += self.liquidity * (self.sqrt_price - sqrt_price);
+                    self.fee_growth_global += fee * remaining / amount_in / self.liquidity;
+                    self.sqrt_price = sqrt_price;
+                    self.tick = tick_at_price(sqrt_price * sqrt_price).max(next_tick);
+
+                    break;
+                }
+
+                amount_out += self.liquidity * (self.sqrt_price - next_sqrt_price);
+                self.fee_growth_global += fee * to_next_tick / amount_in / self.liquidity;
+                remaining -= to_next_tick;
+            }
+
+            // cross the tick going down
+            let tick = self.ticks.get_mut(&next_tick).unwrap();
+            tick.fee_growth_outside = self.fee_growth_global - tick.fee_growth_outside;
+
+            self.liquidity = (self.liquidity - tick.liquidity_net).max(0.0);
+            self.sqrt_price = next_sqrt_price;
+            self.tick = next_tick - 1;
+        }
+
+        Ok(amount_out)
+    }
+
+    fn update_tick(&mut self, tick: i32, liquidity_net: f64, positions: i32) {
+
+        // by convention all fee growth so far happened below a new tick at or under the price
+        let fee_growth_outside = if tick <= self.tick { self.fee_growth_global } else { 0.0 };
+
+        let info = self.ticks.entry(tick).or_insert(Tick { liquidity_net: 0.0, fee_growth_outside, positions: 0 });
+        info.liquidity_net += liquidity_net;
+        info.positions = info.positions.saturating_add_signed(positions);
+
+        if info.positions == 0 {
+            self.ticks.remove(&tick);
+        }
+    }
+
+    //fee growth per unit of liquidity that happened while the price was inside a range
+    fn fee_growth_inside(&self, lower_tick: i32, upper_tick: i32) -> f64 {
+
+        let outside = |tick: i32| self.ticks.get(&tick).map_or(0.0, |info| info.fee_growth_outside);
+
+        let below = if self.tick >= lower_tick { outside(lower_tick) } else { self.fee_growth_global - outside(lower_tick) };
+        let above = if self.tick < upper_tick { outside(upper_tick) } else { self.fee_growth_global - outside(upper_tick) };
+
+        self.fee_growth_global - below - above
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+
+        (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    //from_token that takes the price from where it is down to a tick, with one range's liquidity
+    fn to_tick(pool: &ConcentratedPool, tick: i32) -> f64 {
+
+        pool.active_liquidity() * (1.0 / sqrt_price_at_tick(tick) - 1.0 / pool.sqrt_price)
+    }
+
+    #[test]
+    fn ticks_and_prices_agree() {
+
+        for tick in [-887_272, -100, -1, 0, 1, 100, 887_272] {
+            assert_eq!(tick_at_price(price_at_tick(tick) * (1.0 + 1e-9)), tick);
+        }
+
+        assert_eq!(tick_at_price(1.00005), 0);
+        assert_eq!(tick_at_price(0.99995), -1);
+    }
+
+    #[test]
+    fn swap_inside_a_range_keeps_its_liquidity() {
+
+        let mut pool = ConcentratedPool::new(1.0);
+        pool.add_position(-100, 100, 1_000_000.0).unwrap();
+
+        let amount_out = pool.swap(1_000.0, 0.0).unwrap();
+
+        // x * y holds inside a range: L / sqrt(p) goes up by the amount in
+        let sqrt_price = 1_000_000.0 / (1_000_000.0 + 1_000.0);
+        assert!(close(amount_out, 1_000_000.0 * (1.0 - sqrt_price)));
+        assert!(close(pool.price(), sqrt_price * sqrt_price));
+        assert_eq!(pool.active_liquidity(), 1_000_000.0);
+        assert_eq!(pool.tick, tick_at_price(pool.price()));
+    }
+
+    #[test]
+    fn crossing_a_tick_switches_liquidity() {
+
+        let mut pool = ConcentratedPool::new(1.0);
+        pool.add_position(-100, 100, 1_000_000.0).unwrap();
+        pool.add_position(-300, -100, 2_000_000.0).unwrap();
+
+        assert_eq!(pool.active_liquidity(), 1_000_000.0);
+
+        let first = to_tick(&pool, -100);
+        let out_first = 1_000_000.0 * (1.0 - sqrt_price_at_tick(-100));
+
+        // past -100 only the lower range is left, with twice the liquidity
+        let amount_out = pool.swap(first + 500.0, 0.0).unwrap();
+        let sqrt_price = 2_000_000.0 * sqrt_price_at_tick(-100) / (2_000_000.0 + 500.0 * sqrt_price_at_tick(-100));
+
+        assert_eq!(pool.active_liquidity(), 2_000_000.0);
+        assert!(pool.tick < -100 && pool.tick >= -300);
+        assert!(close(pool.sqrt_price, sqrt_price));
+        assert!(close(amount_out, out_first + 2_000_000.0 * (sqrt_price_at_tick(-100) - sqrt_price)));
+    }
+
+    #[test]
+    fn swap_jumps_gaps_and_stops_at_the_last_tick() {
+
+        let mut pool = ConcentratedPool::new(1.0);
+        pool.add_position(-100, 100, 1_000_000.0).unwrap();
+        pool.add_position(-500, -400, 1_000_000.0).unwrap();
+
+        // through the first range and the empty gap into the second one
+        pool.swap(to_tick(&pool, -100) + 1.0, 0.0).unwrap();
+
+        assert!(pool.tick < -400);
+        assert_eq!(pool.active_liquidity(), 1_000_000.0);
+
+        // nothing below -500
+        assert!(matches!(pool.swap(1e12, 0.0), Err(SwapError::NotEnoughLiquidity)));
+    }
+
+    #[test]
+    fn fees_go_to_the_ranges_a_swap_went_through() {
+
+        let mut pool = ConcentratedPool::new(1.0);
+        let upper = pool.add_position(-100, 100, 1_000_000.0).unwrap();
+        let lower = pool.add_position(-300, -100, 1_000_000.0).unwrap();
+        let idle = pool.add_position(200, 300, 1_000_000.0).unwrap();
+
+        // as much from_token in each range
+        let amount_in = 2.0 * to_tick(&pool, -100);
+        pool.swap(amount_in, 30.0).unwrap();
+
+        let upper_fees = pool.position_amounts(upper).unwrap().fees;
+        let lower_fees = pool.position_amounts(lower).unwrap().fees;
+
+        assert!(close(upper_fees + lower_fees, 30.0));
+        assert!((upper_fees - lower_fees).abs() < 1e-6);
+        assert_eq!(pool.position_amounts(idle).unwrap().fees, 0.0);
+
+        // taking the whole range out pays its fees and frees its ticks
+        let removed = pool.remove_position(upper, 1_000_000.0).unwrap();
+        assert!(close(removed.fees, upper_fees));
+        assert!(!pool.ticks.contains_key(&100));
+    }
+}
@@ -0,0 +1,641 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::aggregator::{Aggregator, DEFAULT_AGGREGATOR_GAS_UNITS};
+use crate::batch::BatchAuction;
+use crate::bridge::BridgeRoute;
+use crate::concentrated::{ConcentratedPool, tick_at_price};
+use crate::gas::{DEFAULT_GAS_UNITS, GasModel, NetworkGas};
+use crate::exchange::{Exchange, ExchangeData, Network, TokenPairData};
+use crate::pricing::PricingModel;
+use crate::token::{TokenAmount, TokenInfo, TokenRegistry, TokenType};
+
+//the tokens, exchanges and pools that ship with the crate, used when no config file is given
+pub const DEFAULT_CONFIG: &str = include_str!("../exchanges.toml");
+
+//a loaded and validated config file
+pub struct Config {
+
+    pub tokens: TokenRegistry,
+    pub exchanges: Vec<ExchangeData>,
+    pub bridges: Vec<BridgeRoute>,
+    pub auctions: Vec<BatchAuction>,
+    pub aggregators: Vec<Aggregator>,
+    pub gas: GasModel,
+}
+
+//Everything that can go wrong while loading a config file
+#[derive(Debug)]
+pub enum ConfigError {
+
+    Io(String),
+    Parse(String),
+    UnknownExchange(String),
+    UnknownNetwork(String),
+    UnknownToken { symbol: String, network: String },
+    DuplicateToken { symbol: String, network: String },
+    InvalidToken(String),
+    DuplicateNetwork(String),
+    InvalidGasPrice(String),
+    DuplicateExchange { name: String, network: String },
+    InvalidBridge { token: String, from: String, to: String },
+    DuplicateBridge { token: String, from: String, to: String },
+    DuplicatePair { exchange: String, from: String, to: String },
+    SameTokenPair { exchange: String, token: String },
+    InvalidReserve { exchange: String, from: String, to: String },
+    InvalidPricing { exchange: String, from: String, to: String },
+    InvalidFee { exchange: String, from: String, to: String },
+    InvalidAuction { name: String, network: String },
+    InvalidAggregator { name: String, network: String },
+}
+
+impl fmt::Display for ConfigError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read config file: {}", error),
+            ConfigError::Parse(error) => write!(f, "could not parse config file: {}", error),
+            ConfigError::UnknownExchange(name) => write!(f, "unknown exchange `{}`", name),
+            ConfigError::UnknownNetwork(name) => write!(f, "unknown network `{}`", name),
+            ConfigError::UnknownToken { symbol, network } => write!(f, "unknown token `{}` on {}", symbol, network),
+            ConfigError::DuplicateToken { symbol, network } => write!(f, "token `{}` is listed twice on {}", symbol, network),
+            ConfigError::InvalidToken(symbol) => write!(f, "token `{}` has an invalid symbol, decimals or price", symbol),
+            ConfigError::DuplicateNetwork(name) => write!(f, "network `{}` is listed twice", name),
+            ConfigError::InvalidGasPrice(name) => write!(f, "network `{}` has an invalid gas or native token price", name),
+            ConfigError::DuplicateExchange { name, network } => write!(f, "exchange `{}` is listed twice on {}", name, network),
+            ConfigError::InvalidBridge { token, from, to } => write!(f, "the {} bridge from {} to {} has an invalid network, fee or minimum", token, from, to),
+            ConfigError::DuplicateBridge { token, from, to } => write!(f, "the {} bridge from {} to {} is listed twice", token, from, to),
+            ConfigError::DuplicatePair { exchange, from, to } => write!(f, "{} lists the {} -> {} pair twice", exchange, from, to),
+            ConfigError::SameTokenPair { exchange, token } => write!(f, "{} has a {} -> {} pair", exchange, token, token),
+            ConfigError::InvalidReserve { exchange, from, to } => write!(f, "{} has an invalid reserve on the {} -> {} pair", exchange, from, to),
+            ConfigError::InvalidPricing { exchange, from, to } => write!(f, "{} has invalid pricing on the {} -> {} pair", exchange, from, to),
+            ConfigError::InvalidFee { exchange, from, to } => write!(f, "{} charges 100% or more in fees on the {} -> {} pair", exchange, from, to),
+            ConfigError::InvalidAuction { name, network } => write!(f, "the {} batch auction on {} has a window of 0 blocks", name, network),
+            ConfigError::InvalidAggregator { name, network } => write!(f, "the {} aggregator on {} charges 100% or more in fees", name, network),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+
+// The file layout. Names are kept as strings here and checked in `validate`,
+// so a typo gives a clear error instead of a generic parse failure.
+
+#[derive(Deserialize)]
+struct ConfigFile {
+
+    #[serde(default)]
+    networks: Vec<NetworkConfig>,
+    tokens: Vec<TokenConfig>,
+    exchanges: Vec<ExchangeConfig>,
+    #[serde(default)]
+    bridges: Vec<BridgeConfig>,
+    #[serde(default)]
+    batch_auctions: Vec<BatchAuctionConfig>,
+    #[serde(default)]
+    aggregators: Vec<AggregatorConfig>,
+}
+
+#[derive(Deserialize)]
+struct NetworkConfig {
+
+    name: String,
+    native_token: String,
+    native_usd_price: f64,
+    gas_price_gwei: f64,
+}
+
+#[derive(Deserialize)]
+struct TokenConfig {
+
+    symbol: String,
+    name: String,
+    decimals: u8,
+    network: String,
+    contract_address: Option<String>,
+    usd_price: f64,
+}
+
+#[derive(Deserialize)]
+struct ExchangeConfig {
+
+    name: String,
+    network: String,
+    gas_units: Option<u64>,    //DEFAULT_GAS_UNITS when left out
+    #[serde(default)]
+    maker_strategies: bool,    //users can place Carbon style strategies on its pairs
+    pairs: Vec<PairConfig>,
+}
+
+#[derive(Deserialize)]
+struct PairConfig {
+
+    from: String,
+    to: String,
+    from_reserve: String,    //whole tokens, e.g. "50000000" or "1500.25"
+    to_reserve: String,
+    pricing: PricingConfig,
+    #[serde(default)]
+    fee_bps: u32,            //LP fee tier in basis points, 0 when left out
+    #[serde(default)]
+    protocol_fee_bps: u32,
+}
+
+#[derive(Deserialize)]
+struct BridgeConfig {
+
+    token: String,
+    from: String,           //source network
+    to: String,             //destination network
+    fee_bps: u32,
+    min_amount: String,     //whole tokens on the source network
+    delay_blocks: u64,
+}
+
+#[derive(Deserialize)]
+struct BatchAuctionConfig {
+
+    name: String,
+    network: String,
+    window_blocks: u64,     //blocks a batch collects orders before it settles
+}
+
+#[derive(Deserialize)]
+struct AggregatorConfig {
+
+    name: String,
+    network: String,
+    fee_bps: u32,              //taken from the output
+    gas_units: Option<u64>,    //on top of the venues it goes through, DEFAULT_AGGREGATOR_GAS_UNITS when left out
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "model", rename_all = "snake_case")]
+enum PricingConfig {
+
+    FixedRate { rate: f64 },
+    ConstantProduct,
+    StableSwap { amplification: f64 },
+    Concentrated {
+        price: Option<f64>,        //to_token per from_token, to_reserve / from_reserve when left out
+        ranges: Vec<RangeConfig>,
+    },
+}
+
+//Liquidity a concentrated pool starts with between two prices. The pool's reserves are worth
+//a total amount at its price, each range gets a part of it in proportion to its weight.
+#[derive(Deserialize)]
+struct RangeConfig {
+
+    lower: f64,
+    upper: f64,
+    weight: f64,
+}
+
+
+//Build a concentrated pool at `price` (whole tokens) with the reserves' total value spread over
+//the ranges by weight. None when the price or a range doesn't make sense.
+fn concentrated_pool(price: f64, ranges: &[RangeConfig], from_reserve: TokenAmount, to_reserve: TokenAmount) -> Option<PricingModel> {
+
+    // ticks and liquidity are in base units
+    let scale = 10f64.powi(to_reserve.decimals as i32 - from_reserve.decimals as i32);
+    let total_weight: f64 = ranges.iter().map(|range| range.weight).sum();
+
+    if !(price > 0.0 && price.is_finite()) || ranges.is_empty() {
+        return None;
+    }
+
+    let mut pool = ConcentratedPool::new(price * scale);
+    let value = from_reserve.units as f64 * price * scale + to_reserve.units as f64;
+
+    for range in ranges {
+
+        if !(range.lower > 0.0 && range.upper > range.lower && range.upper.is_finite() && range.weight.is_finite() && range.weight > 0.0) {
+            return None;
+        }
+
+        let lower_tick = tick_at_price(range.lower * scale);
+        let upper_tick = tick_at_price(range.upper * scale);
+
+        // value of one unit of liquidity in the range, in to_token at the pool price
+        let (from_units, to_units) = pool.amounts_for(lower_tick, upper_tick, 1.0);
+        let liquidity = value * range.weight / total_weight / (from_units * price * scale + to_units);
+
+        pool.add_position(lower_tick, upper_tick, liquidity).ok()?;
+    }
+
+    Some(PricingModel::Concentrated(pool))
+}
+
+
+//load tokens, exchanges and bridges from a TOML or JSON file, picked by the file extension
+pub fn load_config(path: &Path) -> Result<Config, ConfigError> {
+
+    let contents = fs::read_to_string(path).map_err(|error| ConfigError::Io(error.to_string()))?;
+
+    let is_json = path.extension().is_some_and(|extension| extension == "json");
+
+    if is_json {
+        parse_json(&contents)
+    } else {
+        parse_toml(&contents)
+    }
+}
+
+pub fn parse_toml(contents: &str) -> Result<Config, ConfigError> {
+
+    let file: ConfigFile = toml::from_str(contents).map_err(|error| ConfigError::Parse(error.to_string()))?;
+    validate(file)
+}
+
+pub fn parse_json(contents: &str) -> Result<Config, ConfigError> {
+
+    let file: ConfigFile = serde_json::from_str(contents).map_err(|error| ConfigError::Parse(error.to_string()))?;
+    validate(file)
+}
+
+//the default config is part of the crate, so a broken one is a bug rather than a user error
+pub fn load_default() -> Config {
+
+    parse_toml(DEFAULT_CONFIG).expect("the default exchanges.toml is invalid")
+}
+
+
+//turn the raw file into tokens, exchanges and bridges, checking every name and number on the way
+fn validate(file: ConfigFile) -> Result<Config, ConfigError> {
+
+    let mut networks: Vec<NetworkGas> = Vec::new();
+
+    for network_config in file.networks {
+
+        let network = Network::from_name(&network_config.name)
+            .ok_or_else(|| ConfigError::UnknownNetwork(network_config.name.clone()))?;
+
+        if networks.iter().any(|gas| gas.network == network) {
+            return Err(ConfigError::DuplicateNetwork(network_config.name));
+        }
+
+        let is_price = |price: f64| price.is_finite() && price >= 0.0;
+
+        if !is_price(network_config.gas_price_gwei) || !is_price(network_config.native_usd_price) {
+            return Err(ConfigError::InvalidGasPrice(network_config.name));
+        }
+
+        networks.push(NetworkGas {
+            network,
+            native_token: network_config.native_token,
+            native_usd_price: network_config.native_usd_price,
+            gas_price_gwei: network_config.gas_price_gwei,
+        });
+    }
+
+    let mut tokens = TokenRegistry::new();
+
+    for token in file.tokens {
+
+        let network = Network::from_name(&token.network)
+            .ok_or_else(|| ConfigError::UnknownNetwork(token.network.clone()))?;
+
+        // 38 decimals already fills a u128 with a single token
+        // gas is priced in the token by dividing by its price, so it can't be zero
+        if token.symbol.is_empty() || token.decimals > 30 || !token.usd_price.is_finite() || token.usd_price <= 0.0 {
+            return Err(ConfigError::InvalidToken(token.symbol));
+        }
+
+        let info = TokenInfo {
+            symbol: TokenType::new(&token.symbol),
+            name: token.name,
+            decimals: token.decimals,
+            network,
+            contract_address: token.contract_address,
+            usd_price: token.usd_price,
+        };
+
+        tokens.register(info).map_err(|_| ConfigError::DuplicateToken { symbol: token.symbol, network: token.network })?;
+    }
+
+    let mut exchanges: Vec<ExchangeData> = Vec::new();
+
+    for exchange_config in file.exchanges {
+
+        let exchange_name = Exchange::from_name(&exchange_config.name)
+            .ok_or_else(|| ConfigError::UnknownExchange(exchange_config.name.clone()))?;
+
+        let supported_network = Network::from_name(&exchange_config.network)
+            .ok_or_else(|| ConfigError::UnknownNetwork(exchange_config.network.clone()))?;
+
+        // the same exchange may be deployed on several networks, but only once on each
+        if exchanges.iter().any(|exchange| exchange.exchange_name == exchange_name && exchange.supported_network == supported_network) {
+            return Err(ConfigError::DuplicateExchange { name: exchange_config.name, network: exchange_config.network });
+        }
+
+        let mut token_pairs: Vec<TokenPairData> = Vec::new();
+
+        for pair in exchange_config.pairs {
+
+            // both tokens have to be registered on the exchange's network
+            let unknown_token = |symbol: &str| ConfigError::UnknownToken {
+                symbol: symbol.to_string(),
+                network: exchange_config.network.clone(),
+            };
+
+            let from_token = TokenType::new(&pair.from);
+            let to_token = TokenType::new(&pair.to);
+
+            let from_decimals = tokens.decimals(&from_token, supported_network).map_err(|_| unknown_token(&pair.from))?;
+            let to_decimals = tokens.decimals(&to_token, supported_network).map_err(|_| unknown_token(&pair.to))?;
+
+            if from_token == to_token {
+                return Err(ConfigError::SameTokenPair { exchange: exchange_config.name, token: pair.from });
+            }
+
+            if token_pairs.iter().any(|existing| existing.from_token == from_token && existing.to_token == to_token) {
+                return Err(ConfigError::DuplicatePair { exchange: exchange_config.name, from: pair.from, to: pair.to });
+            }
+
+            let invalid_reserve = || ConfigError::InvalidReserve {
+                exchange: exchange_config.name.clone(),
+                from: pair.from.clone(),
+                to: pair.to.clone(),
+            };
+
+            let from_reserve = TokenAmount::parse(&pair.from_reserve, from_decimals).map_err(|_| invalid_reserve())?;
+            let to_reserve = TokenAmount::parse(&pair.to_reserve, to_decimals).map_err(|_| invalid_reserve())?;
+
+            if from_reserve.is_zero() || to_reserve.is_zero() {
+                return Err(invalid_reserve());
+            }
+
+            let invalid_pricing = || ConfigError::InvalidPricing {
+                exchange: exchange_config.name.clone(),
+                from: pair.from.clone(),
+                to: pair.to.clone(),
+            };
+
+            let pricing = match pair.pricing {
+                PricingConfig::FixedRate { rate } if rate.is_finite() && rate > 0.0 => PricingModel::FixedRate(rate),
+                PricingConfig::ConstantProduct => PricingModel::ConstantProduct,
+                PricingConfig::StableSwap { amplification } if amplification.is_finite() && amplification > 0.0 => PricingModel::StableSwap { amplification },
+                PricingConfig::Concentrated { price, ranges } => {
+                    let price = price.unwrap_or(to_reserve.to_f64() / from_reserve.to_f64());
+                    concentrated_pool(price, &ranges, from_reserve, to_reserve).ok_or_else(invalid_pricing)?
+                },
+                _ => return Err(invalid_pricing()),
+            };
+
+            // a concentrated pool holds what its ranges hold, not the reserves it was sized with
+            let (from_reserve, to_reserve) = match &pricing {
+                PricingModel::Concentrated(pool) => {
+                    let (from_units, to_units) = pool.positions().iter()
+                        .map(|position| pool.amounts_for(position.lower_tick, position.upper_tick, position.liquidity))
+                        .fold((0.0, 0.0), |(from, to), (position_from, position_to)| (from + position_from, to + position_to));
+
+                    (TokenAmount::from_units(from_units.floor() as u128, from_decimals), TokenAmount::from_units(to_units.floor() as u128, to_decimals))
+                },
+                _ => (from_reserve, to_reserve),
+            };
+
+            if pair.fee_bps.saturating_add(pair.protocol_fee_bps) >= 10_000 {
+                return Err(ConfigError::InvalidFee { exchange: exchange_config.name, from: pair.from, to: pair.to });
+            }
+
+            token_pairs.push(TokenPairData {
+                from_token,
+                to_token,
+                from_reserve,
+                to_reserve,
+                pricing,
+                fee_bps: pair.fee_bps,
+                protocol_fee_bps: pair.protocol_fee_bps,
+                // the liquidity a pool is loaded with gets one share per base unit of from_token
+                total_shares: from_reserve.units,
+            });
+        }
+
+        let gas_units = exchange_config.gas_units.unwrap_or(DEFAULT_GAS_UNITS);
+
+        exchanges.push(ExchangeData {
+            exchange_name,
+            supported_network,
+            token_pairs,
+            gas_units,
+            lp_fees: HashMap::new(),
+            protocol_fees: HashMap::new(),
+            maker_strategies: exchange_config.maker_strategies,
+            strategies: Vec::new(),
+            next_strategy_id: 0,
+        });
+    }
+
+    let mut bridges: Vec<BridgeRoute> = Vec::new();
+
+    for bridge in file.bridges {
+
+        let invalid_bridge = || ConfigError::InvalidBridge { token: bridge.token.clone(), from: bridge.from.clone(), to: bridge.to.clone() };
+
+        let from_network = Network::from_name(&bridge.from).ok_or_else(|| ConfigError::UnknownNetwork(bridge.from.clone()))?;
+        let to_network = Network::from_name(&bridge.to).ok_or_else(|| ConfigError::UnknownNetwork(bridge.to.clone()))?;
+
+        if from_network == to_network || bridge.fee_bps >= 10_000 {
+            return Err(invalid_bridge());
+        }
+
+        // the token has to exist on both ends of the bridge
+        let token = TokenType::new(&bridge.token);
+        let from_decimals = tokens.decimals(&token, from_network)
+            .map_err(|_| ConfigError::UnknownToken { symbol: bridge.token.clone(), network: bridge.from.clone() })?;
+        let to_decimals = tokens.decimals(&token, to_network)
+            .map_err(|_| ConfigError::UnknownToken { symbol: bridge.token.clone(), network: bridge.to.clone() })?;
+
+        let min_amount = TokenAmount::parse(&bridge.min_amount, from_decimals).map_err(|_| invalid_bridge())?;
+
+        if bridges.iter().any(|route| route.token == token && route.from_network == from_network && route.to_network == to_network) {
+            return Err(ConfigError::DuplicateBridge { token: bridge.token, from: bridge.from, to: bridge.to });
+        }
+
+        bridges.push(BridgeRoute {
+            token,
+            from_network,
+            to_network,
+            to_decimals,
+            fee_bps: bridge.fee_bps,
+            min_amount,
+            delay_blocks: bridge.delay_blocks,
+        });
+    }
+
+    let mut auctions: Vec<BatchAuction> = Vec::new();
+
+    for auction in file.batch_auctions {
+
+        let exchange_name = Exchange::from_name(&auction.name).ok_or_else(|| ConfigError::UnknownExchange(auction.name.clone()))?;
+        let network = Network::from_name(&auction.network).ok_or_else(|| ConfigError::UnknownNetwork(auction.network.clone()))?;
+
+        // an exchange on a network is either pools or a batch auction, not both
+        let listed = exchanges.iter().any(|exchange| exchange.exchange_name == exchange_name && exchange.supported_network == network)
+            || auctions.iter().any(|existing| existing.exchange_name == exchange_name && existing.network == network);
+
+        if listed {
+            return Err(ConfigError::DuplicateExchange { name: auction.name, network: auction.network });
+        }
+
+        if auction.window_blocks == 0 {
+            return Err(ConfigError::InvalidAuction { name: auction.name, network: auction.network });
+        }
+
+        auctions.push(BatchAuction::new(exchange_name, network, auction.window_blocks));
+    }
+
+    let mut aggregators: Vec<Aggregator> = Vec::new();
+
+    for aggregator in file.aggregators {
+
+        let exchange_name = Exchange::from_name(&aggregator.name).ok_or_else(|| ConfigError::UnknownExchange(aggregator.name.clone()))?;
+        let network = Network::from_name(&aggregator.network).ok_or_else(|| ConfigError::UnknownNetwork(aggregator.network.clone()))?;
+
+        // an aggregator holds no pools, so it can't also be listed as an exchange or a batch auction
+        let listed = exchanges.iter().any(|exchange| exchange.exchange_name == exchange_name && exchange.supported_network == network)
+            || auctions.iter().any(|auction| auction.exchange_name == exchange_name && auction.network == network)
+            || aggregators.iter().any(|existing| existing.exchange_name == exchange_name && existing.network == network);
+
+        if listed {
+            return Err(ConfigError::DuplicateExchange { name: aggregator.name, network: aggregator.network });
+        }
+
+        if aggregator.fee_bps >= 10_000 {
+            return Err(ConfigError::InvalidAggregator { name: aggregator.name, network: aggregator.network });
+        }
+
+        let gas_units = aggregator.gas_units.unwrap_or(DEFAULT_AGGREGATOR_GAS_UNITS);
+        aggregators.push(Aggregator::new(exchange_name, network, aggregator.fee_bps, gas_units));
+    }
+
+    let gas = GasModel::new(networks, &tokens);
+
+    Ok(Config { tokens, exchanges, bridges, auctions, aggregators, gas })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TOKENS: &str = r#"
+[[tokens]]
+symbol = "USDC"
+name = "USD Coin"
+decimals = 6
+network = "Ethereum"
+usd_price = 1.0
+
+[[tokens]]
+symbol = "USDT"
+name = "Tether"
+decimals = 6
+network = "Ethereum"
+usd_price = 1.0
+"#;
+
+    //a config with the two tokens and one Uniswap pair priced by `pricing`
+    fn with_pair(pricing: &str) -> Result<Config, ConfigError> {
+
+        parse_toml(&format!(r#"{}
+[[exchanges]]
+name = "Uniswap"
+network = "Ethereum"
+
+[[exchanges.pairs]]
+from = "USDC"
+to = "USDT"
+from_reserve = "1000000"
+to_reserve = "1000000"
+pricing = {}
+"#, TOKENS, pricing))
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+
+        assert!(parse_toml(DEFAULT_CONFIG).is_ok());
+    }
+
+    #[test]
+    fn pricing_has_to_be_finite_and_positive() {
+
+        assert!(with_pair(r#"{ model = "fixed_rate", rate = 1.0 }"#).is_ok());
+        assert!(with_pair(r#"{ model = "stable_swap", amplification = 100.0 }"#).is_ok());
+
+        for pricing in [
+            r#"{ model = "fixed_rate", rate = inf }"#,
+            r#"{ model = "fixed_rate", rate = nan }"#,
+            r#"{ model = "fixed_rate", rate = 0.0 }"#,
+            r#"{ model = "stable_swap", amplification = inf }"#,
+            r#"{ model = "stable_swap", amplification = -1.0 }"#,
+            r#"{ model = "concentrated", ranges = [{ lower = 0.9, upper = 1.1, weight = inf }] }"#,
+            r#"{ model = "concentrated", price = inf, ranges = [{ lower = 0.9, upper = 1.1, weight = 1.0 }] }"#,
+        ] {
+            assert!(matches!(with_pair(pricing), Err(ConfigError::InvalidPricing { .. })), "{}", pricing);
+        }
+    }
+
+    #[test]
+    fn token_price_and_decimals_are_checked() {
+
+        for (decimals, usd_price) in [("6", "inf"), ("6", "nan"), ("6", "0.0"), ("31", "1.0")] {
+            let config = format!(r#"
+[[tokens]]
+symbol = "USDC"
+name = "USD Coin"
+decimals = {}
+network = "Ethereum"
+usd_price = {}
+
+[[exchanges]]
+name = "Uniswap"
+network = "Ethereum"
+pairs = []
+"#, decimals, usd_price);
+
+            assert!(matches!(parse_toml(&config), Err(ConfigError::InvalidToken(symbol)) if symbol == "USDC"), "{} {}", decimals, usd_price);
+        }
+    }
+
+    #[test]
+    fn pairs_need_known_tokens_listed_once() {
+
+        let unknown = parse_toml(&format!(r#"{}
+[[exchanges]]
+name = "Uniswap"
+network = "Ethereum"
+
+[[exchanges.pairs]]
+from = "USDC"
+to = "DAI"
+from_reserve = "1000000"
+to_reserve = "1000000"
+pricing = {{ model = "constant_product" }}
+"#, TOKENS));
+        assert!(matches!(unknown, Err(ConfigError::UnknownToken { symbol, .. }) if symbol == "DAI"));
+
+        let pair = r#"
+[[exchanges.pairs]]
+from = "USDC"
+to = "USDT"
+from_reserve = "1000000"
+to_reserve = "1000000"
+pricing = { model = "constant_product" }
+"#;
+        let duplicate = parse_toml(&format!(r#"{}
+[[exchanges]]
+name = "Uniswap"
+network = "Ethereum"
+{}{}"#, TOKENS, pair, pair));
+        assert!(matches!(duplicate, Err(ConfigError::DuplicatePair { .. })));
+    }
+}
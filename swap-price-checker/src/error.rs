@@ -0,0 +1,85 @@
+use std::fmt;
+
+//Error handling
+#[derive(Debug)]
+pub enum SwapError {
+
+    TokenPairNotSupported,
+    NotEnoughLiquidity,
+    InsufficientBalance,
+    IncorrectNetwork,
+    BalanceNotFound,
+    UserNotFound,
+    SlippageExceeded,
+    InvalidAmount,
+    AmountOverflow,
+    TokenNotSupported,
+    DuplicateToken,
+    BridgeRouteNotSupported,
+    BelowBridgeMinimum,
+    LiquidityNotSupported,
+    PositionNotFound,
+    StrategiesNotSupported,
+    StrategyNotFound,
+    AmountUnderflow,
+}
+
+impl fmt::Display for SwapError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        let message = match self {
+            SwapError::TokenPairNotSupported => "token pair is not supported",
+            SwapError::NotEnoughLiquidity => "not enough liquidity in the pool",
+            SwapError::InsufficientBalance => "insufficient balance",
+            SwapError::IncorrectNetwork => "incorrect network",
+            SwapError::BalanceNotFound => "balance not found",
+            SwapError::UserNotFound => "user not found",
+            SwapError::SlippageExceeded => "slippage exceeded the tolerance",
+            SwapError::InvalidAmount => "invalid amount",
+            SwapError::AmountOverflow => "amount overflow",
+            SwapError::TokenNotSupported => "token is not supported on this network",
+            SwapError::DuplicateToken => "token is already registered on this network",
+            SwapError::BridgeRouteNotSupported => "token can't be bridged between these networks",
+            SwapError::BelowBridgeMinimum => "amount is below the bridge minimum",
+            SwapError::LiquidityNotSupported => "venue doesn't take liquidity deposits",
+            SwapError::PositionNotFound => "liquidity position not found",
+            SwapError::StrategiesNotSupported => "venue doesn't take maker strategies",
+            SwapError::StrategyNotFound => "strategy not found",
+            SwapError::AmountUnderflow => "amount would go below zero",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+impl SwapError {
+
+    //Process exit code for the CLI subcommands, so scripts can tell failures apart.
+    //0 is success, 1 a config or I/O error and 2 a usage error, swap errors start at 10.
+    pub fn exit_code(&self) -> i32 {
+
+        match self {
+            SwapError::TokenPairNotSupported => 10,
+            SwapError::NotEnoughLiquidity => 11,
+            SwapError::InsufficientBalance => 12,
+            SwapError::IncorrectNetwork => 13,
+            SwapError::BalanceNotFound => 14,
+            SwapError::UserNotFound => 15,
+            SwapError::SlippageExceeded => 16,
+            SwapError::InvalidAmount => 17,
+            SwapError::AmountOverflow => 18,
+            SwapError::TokenNotSupported => 19,
+            SwapError::DuplicateToken => 20,
+            SwapError::BridgeRouteNotSupported => 21,
+            SwapError::BelowBridgeMinimum => 22,
+            SwapError::LiquidityNotSupported => 23,
+            SwapError::PositionNotFound => 24,
+            SwapError::StrategiesNotSupported => 25,
+            SwapError::StrategyNotFound => 26,
+            SwapError::AmountUnderflow => 27,
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
@@ -0,0 +1,821 @@
+use std::collections::HashMap;
+
+use crate::error::SwapError;
+use crate::concentrated::{price_at_tick, tick_at_price};
+use crate::pricing::{PricingModel, SwapQuote, stableswap_balance, stableswap_invariant};
+use crate::config;
+use crate::liquidity::{LiquidityDeposit, PositionValue, RangeDeposit, RangeOrder};
+use crate::router::SPLIT_CHUNKS;
+use crate::strategy::{Strategy, StrategyOrder, StrategyParams};
+use crate::token::{TokenAmount, TokenType, mul_div, mul_div_up};
+use crate::venue::Venue;
+
+//list of the exchanges
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Exchange {
+
+   Uniswap,
+   Carbon,
+   CowSwap,
+   Matcha,
+   Sushi,
+   PancakeSwap,
+   QuickSwap,
+}
+
+impl Exchange {
+
+    pub fn all() -> [Exchange; 7] {
+
+        [
+            Exchange::Uniswap, Exchange::Carbon, Exchange::CowSwap, Exchange::Matcha, Exchange::Sushi,
+            Exchange::PancakeSwap, Exchange::QuickSwap,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+
+        match self {
+            Exchange::Uniswap => "Uniswap",
+            Exchange::Carbon => "Carbon",
+            Exchange::CowSwap => "CowSwap",
+            Exchange::Matcha => "Matcha",
+            Exchange::Sushi => "Sushi",
+            Exchange::PancakeSwap => "PancakeSwap",
+            Exchange::QuickSwap => "QuickSwap",
+        }
+    }
+
+    //look an exchange up by its name, as written in config files
+    pub fn from_name(name: &str) -> Option<Exchange> {
+
+        Exchange::all().into_iter().find(|exchange| exchange.as_str() == name)
+    }
+}
+
+//list of networks
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Network {
+
+   Ethereum,
+   BNBChain,
+   Polygon,   
+}
+
+impl Network {
+
+    pub fn all() -> [Network; 3] {
+
+        [Network::Ethereum, Network::BNBChain, Network::Polygon]
+    }
+
+    //look a network up by its name, as written in config files
+    pub fn from_name(name: &str) -> Option<Network> {
+
+        Network::all().into_iter().find(|network| format!("{:?}", network) == name)
+    }
+}
+
+//data for each token pair (USDC to USDT, USDC to DAI etc)
+#[derive(Debug, Clone)]
+pub struct TokenPairData {
+
+    pub from_token: TokenType,
+    pub to_token: TokenType,
+    pub from_reserve: TokenAmount,    //pool reserve of the token being swapped in
+    pub to_reserve: TokenAmount,      //pool reserve of the token being swapped out
+    pub pricing: PricingModel,
+    pub fee_bps: u32,             //LP fee tier, 30 = 0.3%. Stays in the pool
+    pub protocol_fee_bps: u32,    //charged on top of the LP fee and kept by the exchange, 0 for none
+    pub total_shares: u128,       //LP shares issued against the reserves
+}
+
+//Data to be fetched from the mock exchanges
+#[derive(Debug, Clone)]
+pub struct ExchangeData {
+
+    pub exchange_name: Exchange,   //eg uniswap etc
+    pub supported_network: Network,   //network the swap operates on
+    pub token_pairs: Vec<TokenPairData>,    // Each exchange now has a list of token pairs it supports
+    pub gas_units: u64,    //gas one swap on this exchange uses
+    pub lp_fees: HashMap<TokenType, TokenAmount>,         //LP fees earned so far, already part of the reserves
+    pub protocol_fees: HashMap<TokenType, TokenAmount>,   //protocol fees collected so far, held outside the pools
+    pub maker_strategies: bool,    //users can place strategies that trade the exchange's pairs
+    pub strategies: Vec<Strategy>,
+    pub next_strategy_id: u64,
+}
+
+impl TokenPairData {
+
+    //amount of to_token the pool pays out for amount_in of from_token, in whole tokens
+    fn amount_out(&self, amount_in: f64) -> f64 {
+
+        let from_reserve = self.from_reserve.to_f64();
+        let to_reserve = self.to_reserve.to_f64();
+
+        match &self.pricing {
+
+            PricingModel::FixedRate(swap_rate) => amount_in * swap_rate,
+
+            //constant product, the output shrinks as the trade eats into the pool
+            PricingModel::ConstantProduct => (to_reserve * amount_in) / (from_reserve + amount_in),
+
+            PricingModel::StableSwap { amplification } => {
+
+                let d = stableswap_invariant(from_reserve, to_reserve, *amplification);
+
+                //both balances come from the same D so rounding in the invariant cancels out
+                let to_before = stableswap_balance(from_reserve, d, *amplification);
+                let to_after = stableswap_balance(from_reserve + amount_in, d, *amplification);
+
+                (to_before - to_after).max(0.0)
+            }
+
+            //a concentrated pool doesn't price off its reserves, see `quote`
+            PricingModel::Concentrated(_) => 0.0,
+        }
+    }
+
+    //concentrated pools price in base units, this turns their prices into whole tokens
+    fn unit_scale(&self) -> f64 {
+
+        10f64.powi(self.to_reserve.decimals as i32 - self.from_reserve.decimals as i32)
+    }
+
+    //marginal price (to_token per from_token) for the given reserves
+    fn spot_price_at(&self, from_reserve: f64, to_reserve: f64) -> f64 {
+
+        match &self.pricing {
+
+            PricingModel::FixedRate(swap_rate) => *swap_rate,
+
+            PricingModel::ConstantProduct => to_reserve / from_reserve,
+
+            //-dy/dx from the partial derivatives of the invariant
+            PricingModel::StableSwap { amplification } => {
+
+                let ann = amplification * 4.0;
+                let d = stableswap_invariant(from_reserve, to_reserve, *amplification);
+                let d_cubed = d * d * d;
+
+                (ann + d_cubed / (4.0 * from_reserve * from_reserve * to_reserve))
+                    / (ann + d_cubed / (4.0 * from_reserve * to_reserve * to_reserve))
+            }
+
+            //the price the pool's ranges are at, whatever the reserves
+            PricingModel::Concentrated(pool) => pool.price() / self.unit_scale(),
+        }
+    }
+
+    //pool price (to_token per from_token) before fees
+    pub fn mid_price(&self) -> f64 {
+
+        self.spot_price_at(self.from_reserve.to_f64(), self.to_reserve.to_f64())
+    }
+
+    //what a deposit of from_amount takes of the other token and mints in shares. The other
+    //token is rounded up and the shares down, so a deposit never dilutes the other LPs.
+    pub fn quote_liquidity(&self, from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        // liquidity in a concentrated pool goes into a price range instead
+        if let PricingModel::Concentrated(_) = self.pricing {
+            return Err(SwapError::LiquidityNotSupported);
+        }
+
+        if self.from_reserve.is_zero() || self.total_shares == 0 {
+            return Err(SwapError::NotEnoughLiquidity);
+        }
+
+        let to_units = mul_div_up(from_amount.units, self.to_reserve.units, self.from_reserve.units)?;
+
+        Ok(LiquidityDeposit {
+            shares: mul_div(from_amount.units, self.total_shares, self.from_reserve.units)?,
+            from_amount,
+            to_amount: TokenAmount::from_units(to_units, self.to_reserve.decimals),
+        })
+    }
+
+    //the part of both reserves a number of shares is worth, rounded down
+    pub fn liquidity_value(&self, shares: u128) -> Result<PositionValue, SwapError> {
+
+        if let PricingModel::Concentrated(_) = self.pricing {
+            return Err(SwapError::LiquidityNotSupported);
+        }
+
+        if shares > self.total_shares {
+            return Err(SwapError::InsufficientBalance);
+        }
+
+        Ok(PositionValue {
+            from_amount: TokenAmount::from_units(mul_div(self.from_reserve.units, shares, self.total_shares)?, self.from_reserve.decimals),
+            to_amount: TokenAmount::from_units(mul_div(self.to_reserve.units, shares, self.total_shares)?, self.to_reserve.decimals),
+            price: self.mid_price(),
+        })
+    }
+
+    //fee on an amount swapped in, rounded up so the pool never charges less than its tier
+    fn fee_on(amount_in: TokenAmount, bps: u32) -> Result<TokenAmount, SwapError> {
+
+        let units = amount_in.units.checked_mul(bps as u128).ok_or(SwapError::AmountOverflow)?.div_ceil(10_000);
+        Ok(TokenAmount::from_units(units, amount_in.decimals))
+    }
+
+    //price the swap and work out where it leaves the pool. The fees come off the amount
+    //swapped in first, the LP fee stays in the pool and the protocol fee leaves it.
+    pub fn quote(&self, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        self.quote_with(&[], amount_in).map(|(quote, _)| quote)
+    }
+
+    //the curve's output for what's left of a swap after fees, and the price it leaves the pool at
+    fn curve_swap(&self, amount_after_fees: TokenAmount, lp_fee: TokenAmount) -> Result<(TokenAmount, f64), SwapError> {
+
+        match &self.pricing {
+
+            // a concentrated pool moves its own price, so the swap runs on a copy of it
+            PricingModel::Concentrated(pool) => {
+
+                let mut pool = pool.clone();
+                let amount_out = pool.swap(amount_after_fees.units as f64, lp_fee.units as f64)?;
+
+                Ok((TokenAmount::from_units(amount_out.floor() as u128, self.to_reserve.decimals), pool.price() / self.unit_scale()))
+            }
+
+            _ => {
+
+                let received_amount = TokenAmount::from_f64(self.amount_out(amount_after_fees.to_f64()), self.to_reserve.decimals)?;
+                let from_reserve_after = self.from_reserve.to_f64() + amount_after_fees.to_f64() + lp_fee.to_f64();
+
+                Ok((received_amount, self.spot_price_at(from_reserve_after, self.to_reserve.to_f64() - received_amount.to_f64())))
+            }
+        }
+    }
+
+    //Where the part of a swap left after fees goes when maker strategies trade the pair too.
+    //It's handed out in SPLIT_CHUNKS chunks, each to whichever of the pool and the strategy
+    //orders pays the most for it on top of what they were given already, the way
+    //`find_best_split` shares an order between venues.
+    fn fill_plan(&self, strategies: &[Strategy], amount_after_fees: TokenAmount) -> Result<FillPlan, SwapError> {
+
+        let orders: Vec<(usize, &StrategyOrder)> = strategies.iter().enumerate()
+            .filter_map(|(index, strategy)| strategy.order_for(&self.from_token, &self.to_token).map(|order| (index, order)))
+            .filter(|(_, order)| !order.amount.is_zero())
+            .collect();
+
+        if orders.is_empty() {
+            return Ok(FillPlan { pool_in: amount_after_fees, fills: Vec::new() });
+        }
+
+        // what the pool pays for an amount, None once it can't take that much
+        let concentrated = matches!(self.pricing, PricingModel::Concentrated(_));
+        let zero_fee = TokenAmount::zero(amount_after_fees.decimals);
+        let pool_out = |units: u128| -> Option<u128> {
+
+            if !concentrated && units > self.from_reserve.units {
+                return None;
+            }
+
+            self.curve_swap(TokenAmount::from_units(units, amount_after_fees.decimals), zero_fee).ok()
+                .map(|(received_amount, _)| received_amount.units)
+                .filter(|&received| received <= self.to_reserve.units)
+        };
+
+        let chunk = (amount_after_fees.units / SPLIT_CHUNKS as u128).max(1);
+        let mut pool_in = 0;
+        let mut order_in = vec![0u128; orders.len()];
+        let mut remaining = amount_after_fees.units;
+
+        while remaining > 0 {
+
+            let size = chunk.min(remaining);
+
+            // (order, amount given, extra output), None for the pool
+            let mut best: Option<(Option<usize>, u128, u128)> = None;
+
+            if let (Some(before), Some(after)) = (pool_out(pool_in), pool_out(pool_in + size)) {
+                best = Some((None, size, after - before));
+            }
+
+            for (position, (_, order)) in orders.iter().enumerate() {
+
+                // never more than it takes to empty the order
+                let size = size.min(order.amount_to_empty().saturating_sub(order_in[position]));
+                if size == 0 {
+                    continue;
+                }
+
+                let gain = order.amount_out(order_in[position] + size).saturating_sub(order.amount_out(order_in[position]));
+
+                // Compare what each pays per unit, the orders can take less than a full chunk.
+                // The gain scaled to the best's size goes through mul_div, 18 decimal amounts
+                // overflow a plain product; if even the quotient doesn't fit it pays more.
+                let better = match best {
+                    Some((_, best_size, best_gain)) => mul_div(gain, best_size, size).map_or(true, |scaled| scaled > best_gain),
+                    None => true,
+                };
+
+                if better {
+                    best = Some((Some(position), size, gain));
+                }
+            }
+
+            match best {
+                Some((None, size, _)) => {
+                    pool_in += size;
+                    remaining -= size;
+                },
+                Some((Some(position), size, _)) => {
+                    order_in[position] += size;
+                    remaining -= size;
+                },
+                None => return Err(SwapError::NotEnoughLiquidity),
+            }
+        }
+
+        Ok(FillPlan {
+            pool_in: TokenAmount::from_units(pool_in, amount_after_fees.decimals),
+            fills: orders.iter().zip(order_in).filter(|(_, amount)| *amount > 0).map(|((index, _), amount)| (*index, amount)).collect(),
+        })
+    }
+
+    //Price the swap against the pool and the strategy orders for the pair together. The
+    //fees are charged on the whole amount and stay with the pool as they do without strategies.
+    fn quote_with(&self, strategies: &[Strategy], amount_in: TokenAmount) -> Result<(SwapQuote, FillPlan), SwapError> {
+
+        let lp_fee = Self::fee_on(amount_in, self.fee_bps)?;
+        let protocol_fee = Self::fee_on(amount_in, self.protocol_fee_bps)?;
+        let amount_after_fees = amount_in.checked_sub(lp_fee)?.checked_sub(protocol_fee)?;
+
+        let plan = self.fill_plan(strategies, amount_after_fees)?;
+
+        // a concentrated pool's depth is in its ranges, it runs out in `swap` instead
+        let pool_amount = plan.pool_in.checked_add(lp_fee)?.checked_add(protocol_fee)?;
+        if self.from_reserve < pool_amount && !matches!(self.pricing, PricingModel::Concentrated(_)) {
+            return Err(SwapError::NotEnoughLiquidity);
+        }
+
+        let (mut received_amount, pool_price_after) = self.curve_swap(plan.pool_in, lp_fee)?;
+
+        // a fixed rate pool can be drained, it can't pay out more than it holds
+        if received_amount > self.to_reserve {
+            return Err(SwapError::NotEnoughLiquidity);
+        }
+
+        // the best price on offer is the pool's or an order's, whichever is better
+        let scale = self.unit_scale();
+        let mut spot_price = self.mid_price();
+        let mut price_after = pool_price_after;
+
+        for (index, strategy) in strategies.iter().enumerate() {
+
+            let Some(order) = strategy.order_for(&self.from_token, &self.to_token) else {
+                continue;
+            };
+
+            spot_price = spot_price.max(order.marginal_rate() / scale);
+
+            let mut filled = strategy.clone();
+            if let Some((_, amount)) = plan.fills.iter().find(|(filled_index, _)| *filled_index == index) {
+                let amount_out = filled.fill(&self.from_token, &self.to_token, *amount)?;
+                received_amount = received_amount.checked_add(TokenAmount::from_units(amount_out, self.to_reserve.decimals))?;
+            }
+
+            price_after = price_after.max(filled.order_for(&self.from_token, &self.to_token).unwrap().marginal_rate() / scale);
+        }
+
+        // prices are quoted after fees, so slippage and price impact only measure the curve
+        let fee_factor = 1.0 - (self.fee_bps + self.protocol_fee_bps) as f64 / 10_000.0;
+
+        let quote = SwapQuote {
+            received_amount,
+            spot_price: spot_price * fee_factor,
+            execution_price: received_amount.to_f64() / amount_in.to_f64(),
+            price_after: price_after * fee_factor,
+            lp_fee,
+            protocol_fee,
+        };
+
+        Ok((quote, plan))
+    }
+}
+
+//how a swap is shared between a pool and the strategy orders trading its pair
+struct FillPlan {
+
+    pool_in: TokenAmount,         //after fees
+    fills: Vec<(usize, u128)>,    //index of the strategy and what its order takes in
+}
+
+impl ExchangeData {
+
+
+    //checks if a token pair exists in the exchanges
+    pub fn check_token_pair(&self, from: &TokenType, to: &TokenType) -> Result<bool, SwapError> {
+
+        for pair in &self.token_pairs {
+
+            // If we find a match for both 'from' and 'to' tokens, pair is supported
+            if &pair.from_token == from && &pair.to_token == to {
+
+                return Ok(true);
+            }
+        }
+
+        //If loop completes without returning, the pair is not supported
+        Err(SwapError::TokenPairNotSupported)
+    }
+
+
+    //simulate the token swap
+    pub fn simulate_swap(&self, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        // the "?" will return early with an Err if the pair isn't supported
+        self.check_token_pair(from, to)?;       
+
+        for pair in &self.token_pairs {
+
+            if &pair.from_token == from && &pair.to_token == to {
+
+                // strategies on the pair fill next to the pool
+                return pair.quote_with(&self.strategies, amount).map(|(quote, _)| quote);
+            }
+        }
+
+        // If the loop completes without returning, it means no matching pair was found
+        Err(SwapError::TokenPairNotSupported)
+    }
+
+
+    //Execute a swap on this exchange and return its quote. The pool reserves and the strategy
+    //orders move by exactly what the quote planned, it's worked out once for both, and the
+    //fees are booked.
+    pub fn execute_swap(&mut self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        for pair in &mut self.token_pairs {
+
+            if &pair.from_token == from && &pair.to_token == to {
+
+                let (quote, plan) = pair.quote_with(&self.strategies, amount_in)?;
+
+                // the strategy orders the quote filled take their part first, the pool the rest
+                let mut pool_received = quote.received_amount;
+
+                for (index, strategy_in) in plan.fills {
+                    let amount_out = self.strategies[index].fill(from, to, strategy_in)?;
+                    pool_received = pool_received.checked_sub(TokenAmount::from_units(amount_out, pool_received.decimals))?;
+                }
+
+                let to_reserve = pair.to_reserve.checked_sub(pool_received).map_err(|_| SwapError::NotEnoughLiquidity)?;
+
+                // the LP fee is part of what goes into the pool, the protocol fee isn't
+                pair.from_reserve = pair.from_reserve.checked_add(plan.pool_in.checked_add(quote.lp_fee)?)?;
+                pair.to_reserve = to_reserve;
+
+                // the same swap the quote ran, this time on the pool itself
+                if let PricingModel::Concentrated(pool) = &mut pair.pricing {
+                    pool.swap(plan.pool_in.units as f64, quote.lp_fee.units as f64)?;
+                }
+
+                let lp_fees = self.lp_fees.entry(from.clone()).or_insert(TokenAmount::zero(amount_in.decimals));
+                *lp_fees = lp_fees.checked_add(quote.lp_fee)?;
+
+                let protocol_fees = self.protocol_fees.entry(from.clone()).or_insert(TokenAmount::zero(amount_in.decimals));
+                *protocol_fees = protocol_fees.checked_add(quote.protocol_fee)?;
+
+                return Ok(quote);
+            }
+        }
+
+        Err(SwapError::TokenPairNotSupported)
+    }
+
+
+    //the pair a liquidity operation is for
+    fn pair_mut(&mut self, from: &TokenType, to: &TokenType) -> Result<&mut TokenPairData, SwapError> {
+
+        self.token_pairs.iter_mut()
+            .find(|pair| &pair.from_token == from && &pair.to_token == to)
+            .ok_or(SwapError::TokenPairNotSupported)
+    }
+
+
+    //deposit into a pool in proportion to its reserves and mint LP shares for it
+    pub fn add_liquidity(&mut self, from: &TokenType, to: &TokenType, from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        let pair = self.pair_mut(from, to)?;
+        let deposit = pair.quote_liquidity(from_amount)?;
+
+        pair.from_reserve = pair.from_reserve.checked_add(deposit.from_amount)?;
+        pair.to_reserve = pair.to_reserve.checked_add(deposit.to_amount)?;
+        pair.total_shares = pair.total_shares.checked_add(deposit.shares).ok_or(SwapError::AmountOverflow)?;
+
+        Ok(deposit)
+    }
+
+
+    //burn LP shares and take their part of both reserves out of the pool
+    pub fn remove_liquidity(&mut self, from: &TokenType, to: &TokenType, shares: u128) -> Result<PositionValue, SwapError> {
+
+        let pair = self.pair_mut(from, to)?;
+        let withdrawn = pair.liquidity_value(shares)?;
+
+        pair.from_reserve = pair.from_reserve.checked_sub(withdrawn.from_amount)?;
+        pair.to_reserve = pair.to_reserve.checked_sub(withdrawn.to_amount)?;
+        pair.total_shares -= shares;
+
+        Ok(withdrawn)
+    }
+
+
+    //Place liquidity in a price range of a concentrated pool. The range is snapped to the
+    //ticks around it and gets as much liquidity as the amounts offered cover, what it
+    //actually takes is rounded up but never past the amounts offered.
+    pub fn add_range_liquidity(&mut self, from: &TokenType, to: &TokenType, order: &RangeOrder) -> Result<RangeDeposit, SwapError> {
+
+        let pair = self.pair_mut(from, to)?;
+        let scale = pair.unit_scale();
+
+        let PricingModel::Concentrated(pool) = &mut pair.pricing else {
+            return Err(SwapError::LiquidityNotSupported);
+        };
+
+        if !(order.lower_price > 0.0 && order.upper_price > order.lower_price && order.upper_price.is_finite()) {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let lower_tick = tick_at_price(order.lower_price * scale);
+        let upper_tick = tick_at_price(order.upper_price * scale);
+
+        let liquidity = pool.liquidity_for(lower_tick, upper_tick, order.from_amount.units as f64, order.to_amount.units as f64);
+
+        // ranges narrower than a tick, or amounts that don't cover a single unit of liquidity
+        if lower_tick >= upper_tick || !liquidity.is_finite() || liquidity < 1.0 {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let (from_units, to_units) = pool.amounts_for(lower_tick, upper_tick, liquidity);
+        let from_amount = TokenAmount::from_units((from_units.ceil() as u128).min(order.from_amount.units), order.from_amount.decimals);
+        let to_amount = TokenAmount::from_units((to_units.ceil() as u128).min(order.to_amount.units), order.to_amount.decimals);
+
+        let position_id = pool.add_position(lower_tick, upper_tick, liquidity)?;
+
+        pair.from_reserve = pair.from_reserve.checked_add(from_amount)?;
+        pair.to_reserve = pair.to_reserve.checked_add(to_amount)?;
+
+        Ok(RangeDeposit {
+            position_id,
+            liquidity: liquidity as u128,
+            from_amount,
+            to_amount,
+            lower_price: price_at_tick(lower_tick) / scale,
+            upper_price: price_at_tick(upper_tick) / scale,
+        })
+    }
+
+
+    //take liquidity out of a range position, with its share of the LP fees in from_token
+    pub fn remove_range_liquidity(&mut self, from: &TokenType, to: &TokenType, position_id: u64, liquidity: u128) -> Result<PositionValue, SwapError> {
+
+        let pair = self.pair_mut(from, to)?;
+        let scale = pair.unit_scale();
+
+        let PricingModel::Concentrated(pool) = &mut pair.pricing else {
+            return Err(SwapError::LiquidityNotSupported);
+        };
+
+        let position = pool.positions().iter().find(|position| position.id == position_id).ok_or(SwapError::PositionNotFound)?;
+
+        // liquidity is handed out rounded down, asking for all of it closes the position
+        let liquidity = if liquidity as f64 >= position.liquidity.floor() { position.liquidity } else { liquidity as f64 };
+        let withdrawn = pool.remove_position(position_id, liquidity)?;
+        let price = pool.price() / scale;
+
+        // never more than the pool holds, in case the f64 maths drifted a unit
+        let from_units = ((withdrawn.from_amount + withdrawn.fees).floor() as u128).min(pair.from_reserve.units);
+        let to_units = (withdrawn.to_amount.floor() as u128).min(pair.to_reserve.units);
+
+        let from_amount = TokenAmount::from_units(from_units, pair.from_reserve.decimals);
+        let to_amount = TokenAmount::from_units(to_units, pair.to_reserve.decimals);
+
+        pair.from_reserve = pair.from_reserve.checked_sub(from_amount)?;
+        pair.to_reserve = pair.to_reserve.checked_sub(to_amount)?;
+
+        Ok(PositionValue { from_amount, to_amount, price })
+    }
+
+
+    //what a range position could be withdrawn for right now, fees included
+    pub fn range_value(&self, from: &TokenType, to: &TokenType, position_id: u64) -> Result<PositionValue, SwapError> {
+
+        let pair = self.token_pairs.iter()
+            .find(|pair| &pair.from_token == from && &pair.to_token == to)
+            .ok_or(SwapError::TokenPairNotSupported)?;
+
+        let PricingModel::Concentrated(pool) = &pair.pricing else {
+            return Err(SwapError::LiquidityNotSupported);
+        };
+
+        let value = pool.position_amounts(position_id)?;
+
+        Ok(PositionValue {
+            from_amount: TokenAmount::from_units((value.from_amount + value.fees).floor() as u128, pair.from_reserve.decimals),
+            to_amount: TokenAmount::from_units(value.to_amount.floor() as u128, pair.to_reserve.decimals),
+            price: pool.price() / pair.unit_scale(),
+        })
+    }
+
+
+    //Place a maker strategy on one of the exchange's pairs. Takers fill it in both directions,
+    //so the exchange has to list the pair both ways.
+    pub fn create_strategy(&mut self, owner: &str, params: &StrategyParams) -> Result<Strategy, SwapError> {
+
+        if !self.maker_strategies {
+            return Err(SwapError::StrategiesNotSupported);
+        }
+
+        self.check_token_pair(&params.base, &params.quote)?;
+        self.check_token_pair(&params.quote, &params.base)?;
+
+        let strategy = Strategy::new(self.next_strategy_id, owner, params)?;
+
+        self.next_strategy_id += 1;
+        self.strategies.push(strategy.clone());
+
+        Ok(strategy)
+    }
+
+
+    //take one of the owner's strategies off the exchange, with whatever its orders hold now
+    pub fn withdraw_strategy(&mut self, owner: &str, strategy_id: u64) -> Result<Strategy, SwapError> {
+
+        let index = self.strategies.iter()
+            .position(|strategy| strategy.id == strategy_id && strategy.owner == owner)
+            .ok_or(SwapError::StrategyNotFound)?;
+
+        Ok(self.strategies.remove(index))
+    }
+
+
+    //wrap exchanges as venues the router can use
+    pub fn into_venues(exchanges: Vec<Self>) -> Vec<Box<dyn Venue>> {
+
+        exchanges.into_iter()
+            .map(|exchange| Box::new(exchange) as Box<dyn Venue>)
+            .collect()
+    }
+
+
+    //the mock exchanges as venues the router can use
+    pub fn mock_venues() -> Vec<Box<dyn Venue>> {
+
+        Self::into_venues(Self::mock_swap_data())
+    }
+
+
+    //the exchanges from the default config shipped with the crate
+    pub fn mock_swap_data() -> Vec<Self> {
+
+        config::load_default().exchanges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn usdc() -> TokenType {
+
+        TokenType::new("USDC")
+    }
+
+    fn usdt() -> TokenType {
+
+        TokenType::new("USDT")
+    }
+
+    //a USDC -> USDT constant product pool at par with no fees
+    fn pool(reserve: u64, decimals: u8) -> TokenPairData {
+
+        TokenPairData {
+            from_token: usdc(),
+            to_token: usdt(),
+            from_reserve: TokenAmount::whole(reserve, decimals).unwrap(),
+            to_reserve: TokenAmount::whole(reserve, decimals).unwrap(),
+            pricing: PricingModel::ConstantProduct,
+            fee_bps: 0,
+            protocol_fee_bps: 0,
+            total_shares: TokenAmount::whole(reserve, decimals).unwrap().units,
+        }
+    }
+
+    //a strategy selling USDT for 0.95 to 0.96 USDC, a better deal than the pool at par
+    fn cheap_usdt(amount: u64, decimals: u8) -> Strategy {
+
+        Strategy::new(0, "0xmaker", &StrategyParams {
+            base: usdt(),
+            quote: usdc(),
+            sell_amount: TokenAmount::whole(amount, decimals).unwrap(),
+            sell_low: 0.95,
+            sell_high: 0.96,
+            buy_amount: TokenAmount::zero(decimals),
+            buy_low: 0.9,
+            buy_high: 0.94,
+        }).unwrap()
+    }
+
+    #[test]
+    fn fill_plan_without_strategies_is_all_pool() {
+
+        let amount = TokenAmount::whole(500, 6).unwrap();
+        let plan = pool(1_000_000, 6).fill_plan(&[], amount).unwrap();
+
+        assert_eq!(plan.pool_in, amount);
+        assert!(plan.fills.is_empty());
+    }
+
+    #[test]
+    fn fill_plan_takes_the_cheaper_order_first() {
+
+        let pair = pool(1_000_000, 6);
+        let strategies = [cheap_usdt(1_000, 6)];
+
+        // small enough for the order alone
+        let plan = pair.fill_plan(&strategies, TokenAmount::whole(500, 6).unwrap()).unwrap();
+        assert!(plan.pool_in.is_zero());
+        assert_eq!(plan.fills, vec![(0, TokenAmount::whole(500, 6).unwrap().units)]);
+
+        // the order runs dry, the pool takes the rest
+        let amount = TokenAmount::whole(5_000, 6).unwrap();
+        let plan = pair.fill_plan(&strategies, amount).unwrap();
+        let (_, order_in) = plan.fills[0];
+
+        assert!(order_in <= strategies[0].sell.amount_to_empty());
+        assert!(order_in > TokenAmount::whole(900, 6).unwrap().units);
+        assert_eq!(plan.pool_in.units + order_in, amount.units);
+    }
+
+    #[test]
+    fn fill_plan_leaves_orders_worse_than_the_pool() {
+
+        // selling USDT for 1.05 to 1.06 USDC, the pool at par pays more for a small swap
+        let strategy = Strategy::new(0, "0xmaker", &StrategyParams {
+            base: usdt(),
+            quote: usdc(),
+            sell_amount: TokenAmount::whole(1_000, 6).unwrap(),
+            sell_low: 1.05,
+            sell_high: 1.06,
+            buy_amount: TokenAmount::zero(6),
+            buy_low: 0.9,
+            buy_high: 0.94,
+        }).unwrap();
+
+        let amount = TokenAmount::whole(500, 6).unwrap();
+        let plan = pool(1_000_000, 6).fill_plan(&[strategy], amount).unwrap();
+
+        assert_eq!(plan.pool_in, amount);
+        assert!(plan.fills.is_empty());
+    }
+
+    #[test]
+    fn fill_plan_handles_18_decimal_millions() {
+
+        // chunks of 10^23 base units, their cross products are far past u128
+        let pair = pool(100_000_000, 18);
+        let strategies = [cheap_usdt(1_000_000, 18)];
+        let amount = TokenAmount::whole(2_000_000, 18).unwrap();
+
+        let plan = pair.fill_plan(&strategies, amount).unwrap();
+        let (_, order_in) = plan.fills[0];
+
+        assert!(order_in > TokenAmount::whole(900_000, 18).unwrap().units);
+        assert_eq!(plan.pool_in.units + order_in, amount.units);
+    }
+
+    #[test]
+    fn execution_follows_the_quote() {
+
+        let mut exchange = ExchangeData {
+            exchange_name: Exchange::Carbon,
+            supported_network: Network::Ethereum,
+            token_pairs: vec![pool(1_000_000, 6)],
+            gas_units: 0,
+            lp_fees: HashMap::new(),
+            protocol_fees: HashMap::new(),
+            maker_strategies: true,
+            strategies: vec![cheap_usdt(1_000, 6)],
+            next_strategy_id: 1,
+        };
+
+        let amount = TokenAmount::whole(5_000, 6).unwrap();
+        let quote = exchange.simulate_swap(&usdc(), &usdt(), amount).unwrap();
+        let executed = exchange.execute_swap(&usdc(), &usdt(), amount).unwrap();
+
+        assert_eq!(executed.received_amount, quote.received_amount);
+
+        // what the order and the pool paid out adds up to the quote
+        let order_out = TokenAmount::whole(1_000, 6).unwrap().checked_sub(exchange.strategies[0].sell.amount).unwrap();
+        let pool_out = TokenAmount::whole(1_000_000, 6).unwrap().checked_sub(exchange.token_pairs[0].to_reserve).unwrap();
+        assert_eq!(order_out.checked_add(pool_out).unwrap(), quote.received_amount);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::token::{TokenAmount, TokenRegistry, TokenType};
+
+//gas a single swap uses on a venue that doesn't say otherwise
+pub const DEFAULT_GAS_UNITS: u64 = 150_000;
+
+//what gas costs on one network
+#[derive(Debug, Clone)]
+pub struct NetworkGas {
+
+    pub network: Network,
+    pub native_token: String,     //token gas is paid in, e.g. ETH
+    pub native_usd_price: f64,
+    pub gas_price_gwei: f64,
+}
+
+//Turns the gas a swap uses into what it costs, in USD or in any registered token.
+//Networks without a gas price are treated as free, so routes are ranked on output alone.
+#[derive(Debug, Clone, Default)]
+pub struct GasModel {
+
+    networks: Vec<NetworkGas>,
+    tokens: TokenRegistry,        //for the USD price and decimals of the output token
+}
+
+impl GasModel {
+
+    pub fn new(networks: Vec<NetworkGas>, tokens: &TokenRegistry) -> Self {
+
+        GasModel { networks, tokens: tokens.clone() }
+    }
+
+    pub fn network_gas(&self, network: Network) -> Option<&NetworkGas> {
+
+        self.networks.iter().find(|gas| gas.network == network)
+    }
+
+    //cost of the gas in the network's native token, e.g. 150_000 gas at 20 gwei is 0.003 ETH
+    pub fn cost_native(&self, network: Network, gas_units: u64) -> f64 {
+
+        match self.network_gas(network) {
+            Some(gas) => gas_units as f64 * gas.gas_price_gwei * 1e-9,
+            None => 0.0,
+        }
+    }
+
+    pub fn cost_usd(&self, network: Network, gas_units: u64) -> f64 {
+
+        match self.network_gas(network) {
+            Some(gas) => self.cost_native(network, gas_units) * gas.native_usd_price,
+            None => 0.0,
+        }
+    }
+
+    //cost of the gas expressed in a token on the network, in base units with the given decimals,
+    //so it can be taken off a quote's output
+    pub fn cost_in(&self, network: Network, gas_units: u64, token: &TokenType, decimals: u8) -> Result<TokenAmount, SwapError> {
+
+        let cost_usd = self.cost_usd(network, gas_units);
+
+        if cost_usd == 0.0 {
+            return Ok(TokenAmount::zero(decimals));
+        }
+
+        let info = self.tokens.get(token, network).ok_or(SwapError::TokenNotSupported)?;
+        TokenAmount::from_f64(cost_usd / info.usd_price, decimals)
+    }
+
+    //what a quote is worth once gas is paid, never below zero
+    pub fn net_output(&self, network: Network, gas_units: u64, token: &TokenType, received_amount: TokenAmount) -> Result<TokenAmount, SwapError> {
+
+        let cost = self.cost_in(network, gas_units, token, received_amount.decimals)?;
+
+        Ok(TokenAmount::from_units(received_amount.units.saturating_sub(cost.units), received_amount.decimals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+    use crate::router::Router;
+
+    // Gas at 20 gwei with ETH at $3000, so 150_000 units cost $9. Both exchanges have a
+    // USDC -> USDT pool; Uniswap's is ten times deeper but its swaps use four times the gas.
+    const CONFIG: &str = r#"
+        [[networks]]
+        name = "Ethereum"
+        native_token = "ETH"
+        native_usd_price = 3000.0
+        gas_price_gwei = 20.0
+
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "WETH"
+        name = "Wrapped Ether"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 3000.0
+
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Polygon"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+        gas_units = 600000
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Sushi"
+        network = "Ethereum"
+        gas_units = 100000
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "100000"
+        to_reserve = "100000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    #[test]
+    fn cost_is_converted_at_the_token_price() {
+
+        let gas = parse_toml(CONFIG).unwrap().gas;
+
+        assert_eq!(gas.cost_usd(Network::Ethereum, 150_000), 9.0);
+        assert_eq!(gas.cost_in(Network::Ethereum, 150_000, &TokenType::new("USDC"), 6).unwrap(), TokenAmount::whole(9, 6).unwrap());
+        assert_eq!(gas.cost_in(Network::Ethereum, 150_000, &TokenType::new("WETH"), 18).unwrap(), TokenAmount::parse("0.003", 18).unwrap());
+
+        // a network without a gas price is free, whatever the token
+        assert!(gas.cost_in(Network::Polygon, 150_000, &TokenType::new("WBTC"), 8).unwrap().is_zero());
+        assert!(matches!(gas.cost_in(Network::Ethereum, 150_000, &TokenType::new("WBTC"), 8), Err(SwapError::TokenNotSupported)));
+    }
+
+    #[test]
+    fn net_output_never_goes_below_zero() {
+
+        let gas = parse_toml(CONFIG).unwrap().gas;
+        let usdc = TokenType::new("USDC");
+
+        let net = gas.net_output(Network::Ethereum, 150_000, &usdc, TokenAmount::whole(100, 6).unwrap()).unwrap();
+        assert_eq!(net, TokenAmount::whole(91, 6).unwrap());
+
+        let net = gas.net_output(Network::Ethereum, 150_000, &usdc, TokenAmount::whole(5, 6).unwrap()).unwrap();
+        assert!(net.is_zero());
+    }
+
+    #[test]
+    fn more_output_can_lose_after_gas() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+        let amount = TokenAmount::whole(1_000, 6).unwrap();
+
+        // Uniswap pays about 9 USDT more and costs 30 more in gas
+        let uniswap = venues[0].quote(&usdc, &usdt, amount).unwrap().received_amount;
+        let sushi = venues[1].quote(&usdc, &usdt, amount).unwrap().received_amount;
+        assert!(uniswap > sushi);
+
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &usdc, &usdt, amount).unwrap();
+        assert_eq!(route[0].venue_name, "Sushi");
+
+        // without gas the deeper pool wins
+        let route = Router::find_best_route(&venues, &GasModel::default(), Network::Ethereum, &usdc, &usdt, amount).unwrap();
+        assert_eq!(route[0].venue_name, "Uniswap");
+    }
+}
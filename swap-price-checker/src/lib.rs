@@ -0,0 +1,49 @@
+//! Cyndie Dex quoting engine.
+//!
+//! The library holds everything needed to price and execute mock token swaps:
+//! tokens and amounts, exchanges and their pools, users and the router.
+//! The `swap-price-checker` binary is a thin interactive menu on top of it, with
+//! `quote`, `compare`, `swap` and `balances` subcommands for scripts.
+
+pub mod error;
+pub mod token;
+pub mod pricing;
+pub mod concentrated;
+pub mod exchange;
+pub mod config;
+pub mod user;
+pub mod store;
+pub mod venue;
+pub mod gas;
+pub mod router;
+pub mod bridge;
+pub mod liquidity;
+pub mod batch;
+pub mod aggregator;
+pub mod strategy;
+pub mod swap;
+pub mod report;
+
+pub use error::SwapError;
+pub use token::{Token, TokenAmount, TokenInfo, TokenRegistry, TokenType};
+pub use pricing::{PricingModel, SwapQuote};
+pub use concentrated::{ConcentratedPool, RangeAmounts, RangePosition};
+pub use exchange::{Exchange, ExchangeData, Network, TokenPairData};
+pub use config::{Config, ConfigError};
+pub use user::{User, UserDatabase};
+pub use store::StoreError;
+pub use venue::{StagedVenues, Venue, VenueClone, VenueFees};
+pub use gas::{DEFAULT_GAS_UNITS, GasModel, NetworkGas};
+pub use router::{RouteHop, Router, SwapLeg, find_best_split, find_best_split_with_route};
+pub use bridge::{Bridge, BridgeQuote, BridgeRoute, PendingTransfer};
+pub use batch::{BatchAuction, BatchFill, BatchOrder};
+pub use aggregator::{Aggregator, AggregatorQuote, DEFAULT_AGGREGATOR_GAS_UNITS};
+pub use strategy::{Strategy, StrategyOrder, StrategyParams, place_strategy, withdraw_strategy};
+pub use liquidity::{
+    LiquidityDeposit, LpPosition, LpReturns, PositionValue, PriceRange, RangeDeposit, RangeOrder,
+    add_liquidity, add_range_liquidity, position_value, remove_liquidity,
+};
+pub use report::{
+    BalanceReport, BalanceRow, FeeReport, HopReport, LegReport, OptionReport, OutputFormat, QuoteRow, QuoteTable, Report, SwapReport,
+};
+pub use swap::{DEFAULT_SLIPPAGE_TOLERANCE, Swap, SwapOption, SwapPlan, SwapResult, VenueQuote, compare_quotes, perform_swap, plan_swap};
@@ -0,0 +1,412 @@
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::token::{TokenAmount, TokenType, mul_div};
+use crate::user::User;
+use crate::venue::Venue;
+
+//a deposit into a pool, worked out from its reserves before anything moves
+#[derive(Debug, Clone, Copy)]
+pub struct LiquidityDeposit {
+
+    pub shares: u128,             //LP shares minted for the deposit
+    pub from_amount: TokenAmount,
+    pub to_amount: TokenAmount,   //matching amount of the other token, in proportion to the reserves
+}
+
+//what LP shares can be withdrawn for right now
+#[derive(Debug, Clone, Copy)]
+pub struct PositionValue {
+
+    pub from_amount: TokenAmount,
+    pub to_amount: TokenAmount,
+    pub price: f64,               //pool price (to_token per from_token) before fees
+}
+
+//liquidity offered for a price range of a concentrated pool, the most of each token it may take
+#[derive(Debug, Clone, Copy)]
+pub struct RangeOrder {
+
+    pub lower_price: f64,         //to_token per from_token
+    pub upper_price: f64,
+    pub from_amount: TokenAmount,
+    pub to_amount: TokenAmount,
+}
+
+//a range position opened in a concentrated pool
+#[derive(Debug, Clone, Copy)]
+pub struct RangeDeposit {
+
+    pub position_id: u64,
+    pub liquidity: u128,          //the position's liquidity, rounded down
+    pub from_amount: TokenAmount, //what the range actually took
+    pub to_amount: TokenAmount,
+    pub lower_price: f64,         //range edges once snapped to ticks
+    pub upper_price: f64,
+}
+
+//where a range position sits in its pool
+#[derive(Debug, Clone, Copy)]
+pub struct PriceRange {
+
+    pub position_id: u64,
+    pub lower_price: f64,
+    pub upper_price: f64,
+}
+
+//a user's LP shares in one pool, with what they put in so the returns can be worked out
+#[derive(Debug, Clone)]
+pub struct LpPosition {
+
+    pub venue_name: String,
+    pub network: Network,
+    pub from_token: TokenType,
+    pub to_token: TokenType,
+    pub shares: u128,                  //liquidity for a range position
+    pub deposited_from: TokenAmount,   //what's still in of the original deposit, shrinks on withdrawals
+    pub deposited_to: TokenAmount,
+    pub range: Option<PriceRange>,     //set for positions in a concentrated pool
+}
+
+//how a position did against simply holding the tokens it was opened with
+#[derive(Debug, Clone, Copy)]
+pub struct LpReturns {
+
+    pub value: f64,          //position value now, in to_token at the pool price
+    pub value_held: f64,     //the deposit left in the wallet instead, at the same price
+    pub return_pct: f64,     //value against value_held, fees earned minus impermanent loss
+}
+
+impl LpPosition {
+
+    fn is_pool(&self, venue: &dyn Venue) -> bool {
+
+        venue.name() == self.venue_name && venue.network() == self.network
+    }
+
+    //compare the position with holding the deposit, both valued at the pool's current price
+    pub fn returns(&self, value: &PositionValue) -> LpReturns {
+
+        let position = value.from_amount.to_f64() * value.price + value.to_amount.to_f64();
+        let held = self.deposited_from.to_f64() * value.price + self.deposited_to.to_f64();
+
+        LpReturns {
+            value: position,
+            value_held: held,
+            return_pct: (position / held - 1.0) * 100.0,
+        }
+    }
+}
+
+//the venue an LP position sits in
+fn find_venue<'a>(venues: &'a mut [Box<dyn Venue>], position: &LpPosition) -> Result<&'a mut Box<dyn Venue>, SwapError> {
+
+    venues.iter_mut()
+        .find(|venue| position.is_pool(venue.as_ref()))
+        .ok_or(SwapError::PositionNotFound)
+}
+
+//A zero amount needs no balance. A range on one side of the price only takes one token,
+//and the user may never have held the other.
+fn can_pay(user: &User, token: &TokenType, amount: TokenAmount) -> bool {
+
+    amount.is_zero() || user.has_sufficient_balance(user.network, token, amount)
+}
+
+fn pay(user: &mut User, token: &TokenType, amount: TokenAmount) -> Result<(), SwapError> {
+
+    if amount.is_zero() {
+        return Ok(());
+    }
+
+    user.deduct_balance(user.network, token, amount)
+}
+
+//Deposit into a pool on the user's network. The user puts in `from_amount` and the matching
+//amount of the other token, and gets the LP shares added to their position in that pool.
+pub fn add_liquidity(venues: &mut [Box<dyn Venue>], venue_index: usize, from: &TokenType, to: &TokenType, from_amount: TokenAmount, user: &mut User) -> Result<LiquidityDeposit, SwapError> {
+
+    let venue = venues.get_mut(venue_index).ok_or(SwapError::TokenPairNotSupported)?;
+
+    if venue.network() != user.network {
+        return Err(SwapError::IncorrectNetwork);
+    }
+
+    if from_amount.is_zero() {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    // check both balances before the pool is touched
+    let deposit = venue.quote_liquidity(from, to, from_amount)?;
+
+    if !can_pay(user, from, deposit.from_amount) || !can_pay(user, to, deposit.to_amount) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let deposit = venue.add_liquidity(from, to, from_amount)?;
+
+    pay(user, from, deposit.from_amount)?;
+    pay(user, to, deposit.to_amount)?;
+
+    let venue_name = venue.name().to_string();
+    let network = venue.network();
+
+    // topping up a pool adds to the position that's already there
+    let existing = user.lp_positions.iter_mut()
+        .find(|position| position.venue_name == venue_name && position.network == network && &position.from_token == from && &position.to_token == to);
+
+    match existing {
+        Some(position) => {
+            position.shares = position.shares.checked_add(deposit.shares).ok_or(SwapError::AmountOverflow)?;
+            position.deposited_from = position.deposited_from.checked_add(deposit.from_amount)?;
+            position.deposited_to = position.deposited_to.checked_add(deposit.to_amount)?;
+        },
+        None => user.lp_positions.push(LpPosition {
+            venue_name,
+            network,
+            from_token: from.clone(),
+            to_token: to.clone(),
+            shares: deposit.shares,
+            deposited_from: deposit.from_amount,
+            deposited_to: deposit.to_amount,
+            range: None,
+        }),
+    }
+
+    Ok(deposit)
+}
+
+//Open a range position in a concentrated pool on the user's network. Every range is its own
+//position, so nothing is merged into a position the user already has.
+pub fn add_range_liquidity(venues: &mut [Box<dyn Venue>], venue_index: usize, from: &TokenType, to: &TokenType, order: &RangeOrder, user: &mut User) -> Result<RangeDeposit, SwapError> {
+
+    let venue = venues.get_mut(venue_index).ok_or(SwapError::TokenPairNotSupported)?;
+
+    if venue.network() != user.network {
+        return Err(SwapError::IncorrectNetwork);
+    }
+
+    // the range may take less than offered, but never more
+    if !can_pay(user, from, order.from_amount) || !can_pay(user, to, order.to_amount) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let deposit = venue.add_range_liquidity(from, to, order)?;
+
+    pay(user, from, deposit.from_amount)?;
+    pay(user, to, deposit.to_amount)?;
+
+    user.lp_positions.push(LpPosition {
+        venue_name: venue.name().to_string(),
+        network: venue.network(),
+        from_token: from.clone(),
+        to_token: to.clone(),
+        shares: deposit.liquidity,
+        deposited_from: deposit.from_amount,
+        deposited_to: deposit.to_amount,
+        range: Some(PriceRange {
+            position_id: deposit.position_id,
+            lower_price: deposit.lower_price,
+            upper_price: deposit.upper_price,
+        }),
+    });
+
+    Ok(deposit)
+}
+
+//Burn some of the user's shares in one of their positions and credit their part of both
+//reserves, fees included. The position is closed once it has no shares left. For a range
+//position the shares are its liquidity.
+pub fn remove_liquidity(venues: &mut [Box<dyn Venue>], position_index: usize, shares: u128, user: &mut User) -> Result<PositionValue, SwapError> {
+
+    let position = user.lp_positions.get(position_index).ok_or(SwapError::PositionNotFound)?.clone();
+
+    if shares == 0 {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    if shares > position.shares {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let venue = find_venue(venues, &position)?;
+
+    let withdrawn = match position.range {
+        Some(range) => venue.remove_range_liquidity(&position.from_token, &position.to_token, range.position_id, shares)?,
+        None => venue.remove_liquidity(&position.from_token, &position.to_token, shares)?,
+    };
+
+    user.add_balance(position.network, &position.from_token, withdrawn.from_amount)?;
+    user.add_balance(position.network, &position.to_token, withdrawn.to_amount)?;
+
+    // what's left of the deposit shrinks with the shares, so returns stay comparable
+    let remaining = position.shares - shares;
+    let stored = &mut user.lp_positions[position_index];
+
+    stored.deposited_from.units = mul_div(position.deposited_from.units, remaining, position.shares)?;
+    stored.deposited_to.units = mul_div(position.deposited_to.units, remaining, position.shares)?;
+    stored.shares = remaining;
+
+    if remaining == 0 {
+        user.lp_positions.remove(position_index);
+    }
+
+    Ok(withdrawn)
+}
+
+//what a position could be withdrawn for right now
+pub fn position_value(venues: &[Box<dyn Venue>], position: &LpPosition) -> Result<PositionValue, SwapError> {
+
+    let venue = venues.iter()
+        .find(|venue| position.is_pool(venue.as_ref()))
+        .ok_or(SwapError::PositionNotFound)?;
+
+    match position.range {
+        Some(range) => venue.range_value(&position.from_token, &position.to_token, range.position_id),
+        None => venue.liquidity_value(&position.from_token, &position.to_token, position.shares),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+
+    // USDC -> USDT at 2 USDT a USDC, and a concentrated USDT -> USDC pool just above par
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "2000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "USDT"
+        to = "USDC"
+        from_reserve = "1000000"
+        to_reserve = "1001000"
+        fee_bps = 5
+        pricing = { model = "concentrated", ranges = [{ lower = 0.99, upper = 1.01, weight = 1.0 }] }
+    "#;
+
+    fn token(symbol: &str) -> TokenType {
+
+        TokenType::new(symbol)
+    }
+
+    //a whole number of USDC or USDT
+    fn tokens(whole: u64) -> TokenAmount {
+
+        TokenAmount::whole(whole, 6).unwrap()
+    }
+
+    //a user holding USDT, and USDC unless `usdc_held` is zero
+    fn user(usdt: u64, usdc_held: u64) -> User {
+
+        let mut user = User {
+            network: Network::Ethereum,
+            wallet_address: "0xlp".to_string(),
+            balances: Default::default(),
+            pending_transfers: Vec::new(),
+            lp_positions: Vec::new(),
+        };
+        user.add_balance(Network::Ethereum, &token("USDT"), tokens(usdt)).unwrap();
+        if usdc_held > 0 {
+            user.add_balance(Network::Ethereum, &token("USDC"), tokens(usdc_held)).unwrap();
+        }
+        user
+    }
+
+    #[test]
+    fn deposits_mint_shares_in_proportion_to_the_reserves() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+        let mut user = user(10_000, 10_000);
+
+        // the pool started with a share per USDC unit, 1_000 USDC takes 2_000 USDT with it
+        let deposit = add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), tokens(1_000), &mut user).unwrap();
+        assert_eq!(deposit.shares, tokens(1_000).units);
+        assert_eq!(deposit.to_amount, tokens(2_000));
+        assert_eq!(user.balance(Network::Ethereum, &token("USDC")), Some(tokens(9_000)));
+        assert_eq!(user.balance(Network::Ethereum, &token("USDT")), Some(tokens(8_000)));
+
+        // topping up adds to the same position
+        add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), tokens(500), &mut user).unwrap();
+        assert_eq!(user.lp_positions.len(), 1);
+        assert_eq!(user.lp_positions[0].shares, tokens(1_500).units);
+        assert_eq!(user.lp_positions[0].deposited_to, tokens(3_000));
+
+        // not enough USDT for the matching side, nothing moves
+        let balances = user.balances.clone();
+        let result = add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), tokens(5_000), &mut user);
+        assert!(matches!(result, Err(SwapError::InsufficientBalance)));
+        assert_eq!(user.balances, balances);
+    }
+
+    #[test]
+    fn withdrawals_burn_shares_for_their_part_of_the_reserves() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+        let mut user = user(10_000, 10_000);
+
+        let deposit = add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), tokens(1_000), &mut user).unwrap();
+
+        assert!(matches!(remove_liquidity(&mut venues, 0, 0, &mut user), Err(SwapError::InvalidAmount)));
+        assert!(matches!(remove_liquidity(&mut venues, 0, deposit.shares + 1, &mut user), Err(SwapError::InsufficientBalance)));
+
+        // nothing traded in between, so half the shares are half the deposit
+        let withdrawn = remove_liquidity(&mut venues, 0, deposit.shares / 2, &mut user).unwrap();
+        assert_eq!((withdrawn.from_amount, withdrawn.to_amount), (tokens(500), tokens(1_000)));
+        assert_eq!(user.lp_positions[0].shares, deposit.shares / 2);
+        assert_eq!((user.lp_positions[0].deposited_from, user.lp_positions[0].deposited_to), (tokens(500), tokens(1_000)));
+
+        // the rest closes the position and leaves the pool as it was
+        remove_liquidity(&mut venues, 0, deposit.shares / 2, &mut user).unwrap();
+        assert!(user.lp_positions.is_empty());
+        assert_eq!(user.balance(Network::Ethereum, &token("USDC")), Some(tokens(10_000)));
+        assert_eq!(user.balance(Network::Ethereum, &token("USDT")), Some(tokens(10_000)));
+        assert_eq!(venues[0].liquidity(&token("USDC"), &token("USDT")), Some(tokens(2_000_000)));
+    }
+
+    #[test]
+    fn one_sided_range_needs_none_of_the_other_token() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+
+        // the range is above the pool price, so it only holds USDT and the user never had USDC
+        let mut user = user(1_000, 0);
+        let order = RangeOrder { lower_price: 1.005, upper_price: 1.009, from_amount: tokens(1_000), to_amount: TokenAmount::zero(6) };
+
+        let deposit = add_range_liquidity(&mut venues, 0, &token("USDT"), &token("USDC"), &order, &mut user).unwrap();
+
+        assert!(deposit.to_amount.is_zero());
+        assert!(!deposit.from_amount.is_zero());
+        assert_eq!(user.balance(Network::Ethereum, &token("USDT")), tokens(1_000).checked_sub(deposit.from_amount).ok());
+        assert_eq!(user.balance(Network::Ethereum, &token("USDC")), None);
+        assert_eq!(user.lp_positions.len(), 1);
+    }
+}
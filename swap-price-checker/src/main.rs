@@ -1,711 +1,752 @@
 
-use std::io;
-use std::collections::HashMap;
-
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-//To represent our tokens 
-
-enum TokenType {
-
-   USDC,
-   USDT,
-   BUSD,
-
-}
-
-//list of the exchanges
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Exchange {
-
-   Uniswap,
-   Carbon,
-   CowSwap,
-   Matcha,
-   Sushi,
-}
-
-//list of networks
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Network {
-
-   Ethereum,
-   BNBChain,
-   Polygon,   
-}
-
-//Error handling
-#[derive(Debug)]
-enum SwapError {
-
-    TokenPairNotSupported,
-    NotEnoughLiquidity,
-    InsufficientBalance,
-    IncorrectNetwork,
-    BalanceNotFound,
-    UserNotFound,
-}
-
-
-struct Token {
-
-    token_type: TokenType,
-}
-
-//data for each token pair (USDC to USDT, USDC to DAI etc)
-struct TokenPairData {
-
-    from_token: TokenType,
-    to_token: TokenType,
-    swap_rate: f64,    //swap rate for tokens
-    liquidity: u64,    //liquidity pool for the tokens
-}
-
-//Data to be fetched from the mock exchanges
-struct ExchangeData {
+mod cli;
 
-    exchange_name: Exchange,   //eg uniswap etc
-    supported_network: Network,   //network the swap operates on
-    token_pairs: Vec<TokenPairData>,    // Each exchange now has a list of token pairs it supports
-}
-
-//when user decides to swap
-struct Swap {
-
-    user_wallet_address: String,
-    from_token: Token,
-    to_token: Token,
-    amount: f64,      //amount of from_token to swap
-
-}
-
-struct SwapResult {
-
-    exchange_name: Exchange,
-    received_amount: f64,
-    slippage: f64
-}
-
-#[derive(Clone)]
-struct User {
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process;
 
-    network: Network,
-    wallet_address: String,
-    balances: HashMap<TokenType, f64>,
+use swap_price_checker::config;
+use swap_price_checker::{
+    Aggregator, BatchAuction, Bridge, DEFAULT_SLIPPAGE_TOLERANCE, ExchangeData, GasModel, Network, Swap, Token, TokenAmount, TokenRegistry, TokenType,
+    RangeOrder, Router, Strategy, StrategyParams, SwapLeg, SwapOption, SwapResult, User, UserDatabase, Venue, VenueQuote, add_liquidity, add_range_liquidity, compare_quotes, perform_swap, place_strategy,
+    position_value, remove_liquidity, withdraw_strategy,
+};
 
-}
 
-struct UserDatabase {
+//everything users trade against, shared by every user of the session
+struct Market {
 
-    users: Vec<User>,
+    venues: Vec<Box<dyn Venue>>,
+    bridge: Bridge,
+    auctions: Vec<BatchAuction>,
+    aggregators: Vec<Aggregator>,
 }
 
 
-//implementing functionalities 
-
-impl ExchangeData {
-
-
-    //checks if a token pair exists in the exchanges
-    pub fn check_token_pair(&self, from: TokenType, to: TokenType) -> Result<bool, SwapError> {
-
-        for pair in &self.token_pairs {
+// `user_menu` provides an interactive interface to the user
+//The function allows the user to interact with their account by providing
+//multiple options, such as viewing balances or initiating a swap transaction.
+fn user_menu(wallet_address: &str, registry: &TokenRegistry, market: &mut Market, gas: &GasModel, user_db: &mut UserDatabase) {
 
-            // If we find a match for both 'from' and 'to' tokens, pair is supported
-            if pair.from_token == from && pair.to_token == to {
+    let Market { venues, bridge, auctions, aggregators } = market;
+    
+    loop {
+        // Credit any bridge transfers that have arrived since the last action
+        match bridge.settle(user_db.get_user_by_address_mut(wallet_address).unwrap()) {
+            Ok(arrived) => {
+                for transfer in arrived {
+                    println!("Bridge transfer arrived: {} {} on {:?}", transfer.received_amount, transfer.token, transfer.to_network);
+                }
+            },
+            Err(error) => println!("Bridge settlement failed: {}", error),
+        }
 
-                return Ok(true);
+        // Settle the batch auctions whose window has closed
+        for auction in auctions.iter_mut() {
+            match auction.settle(bridge.current_block(), venues, gas, user_db) {
+                Ok(fills) => {
+                    for fill in &fills {
+                        let order = &fill.order;
+
+                        if fill.refunded {
+                            println!("{} batch: {}'s {} {} -> {} order couldn't meet its limit and was refunded",
+                                auction.exchange_name.as_str(), order.wallet_address, order.amount_in, order.from_token, order.to_token);
+                            continue;
+                        }
+
+                        println!("{} batch settled for {}: {} {} -> {} {}, {} {} matched peer-to-peer at {:.6}",
+                            auction.exchange_name.as_str(), order.wallet_address, order.amount_in, order.from_token, fill.received_amount, order.to_token,
+                            fill.matched_amount, order.from_token, fill.clearing_price);
+
+                        if let Some(direct) = fill.direct_amount {
+                            let difference = fill.received_amount.units as i128 - direct.units as i128;
+                            let sign = if difference < 0 { "-" } else { "+" };
+
+                            println!("  Swapping directly would have paid {} {} after gas ({}{} {})",
+                                direct, order.to_token, sign, TokenAmount::from_units(difference.unsigned_abs(), direct.decimals), order.to_token);
+                        }
+                    }
+                },
+                Err(error) => println!("Batch settlement failed: {}", error),
             }
         }
 
-        //If loop completes without returning, the pair is not supported
-        Err(SwapError::TokenPairNotSupported)
-    }
+        // Keep whatever the last action and the settlements changed
+        save_users(user_db, venues, bridge, auctions);
 
+        println!("--- User Menu ---");
+        println!("1. View balances");
+        println!("2. Initiate swap");
+        println!("3. Bridge tokens");
+        println!("4. Switch network");
+        println!("5. Wait for blocks");
+        println!("6. View exchange fees");
+        println!("7. Add liquidity");
+        println!("8. Remove liquidity");
+        println!("9. Place a batch auction order");
+        println!("10. Place a maker strategy");
+        println!("11. Withdraw a maker strategy");
+        println!("12. Compare quotes");
+        println!("13. Exit");
+        println!("Select an option:");
+        println!();
 
-    //simulate the token swap
-    pub fn simulate_swap(&self, from: TokenType, to: TokenType, amount: f64) -> Result<f64, SwapError> {
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice).expect("Failed to read line");
 
-        // the "?" will return early with an Err if the pair isn't supported
-        self.check_token_pair(from, to)?;       
+        let network = user_db.get_user_by_address(wallet_address).unwrap().network;
 
-        for pair in &self.token_pairs {
+        match choice.trim() {
+            "1" => {
+               
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
+                
+                // View balances
+                let balances: Vec<String> = registry.tokens_on(network).iter()
+                    .filter_map(|info| user.balance(network, &info.symbol).map(|balance| format!("{}: {}", info.symbol, balance)))
+                    .collect();
+
+                println!("Your balances on {:?} are: {}", network, balances.join(", "));
+
+                for position in &user.lp_positions {
+                    match position_value(venues, position) {
+                        Ok(value) => {
+                            let returns = position.returns(&value);
+                            let range = position.range
+                                .map(|range| format!(" in {:.4}-{:.4}", range.lower_price, range.upper_price))
+                                .unwrap_or_default();
+
+                            println!("  LP: {} {} -> {}{} on {:?}, worth {} {} + {} {}, {:+.4}% against holding",
+                                position.venue_name, position.from_token, position.to_token, range, position.network,
+                                value.from_amount, position.from_token, value.to_amount, position.to_token, returns.return_pct);
+                        },
+                        Err(error) => println!("  LP: {} {} -> {}: {}", position.venue_name, position.from_token, position.to_token, error),
+                    }
+                }
 
-            if pair.from_token == from && pair.to_token == to {
+                for order in auctions.iter().flat_map(|auction| auction.orders()).filter(|order| order.wallet_address == wallet_address) {
+                    println!("  Batch order: {} {} -> {}, at least {} {}", order.amount_in, order.from_token, order.to_token, order.min_received, order.to_token);
+                }
 
-                if pair.liquidity as f64 >= amount {
+                let strategies = venues.iter()
+                    .filter(|venue| venue.network() == network)
+                    .flat_map(|venue| venue.strategies().iter().map(move |strategy| (venue.name(), strategy)))
+                    .filter(|(_, strategy)| strategy.owner == wallet_address);
 
-                    return Ok(amount * pair.swap_rate);
-                } else {
+                for (venue_name, strategy) in strategies {
+                    println!("  Strategy #{} on {}: sells {} {} at {}-{}, buys with {} {} at {}-{} {} per {}",
+                        strategy.id, venue_name, strategy.sell.amount, strategy.base, strategy.sell.low_price, strategy.sell.high_price,
+                        strategy.buy.amount, strategy.quote, strategy.buy.low_price, strategy.buy.high_price, strategy.quote, strategy.base);
+                }
 
-                    return Err(SwapError::NotEnoughLiquidity);
+                for transfer in &user.pending_transfers {
+                    println!("  Pending: {} {} from {:?} to {:?}, arrives at block {} (now {})",
+                        transfer.received_amount, transfer.token, transfer.from_network, transfer.to_network,
+                        transfer.arrival_block, bridge.current_block());
                 }
-            }
-        }
+            },
+            "2" => {
+                // Initiate swap 
+                let from_token = select_token("Choose the token you want to swap FROM:", registry, network);
+                let to_token = select_token("Choose the token you want to swap TO:", registry, network);
 
-        // If the loop completes without returning, it means no matching pair was found
-        return Err(SwapError::TokenPairNotSupported)
-    }
+                //incase user chooses the same token to swap.
+                if from_token == to_token {
+                    println!("Both source and destination tokens are the same. Please try again.");
+                    continue;
+                }
 
+                println!("Enter the amount you want to swap:");
+                let mut amount_input = String::new();
+                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
+                let amount = match TokenAmount::parse(amount_input.trim(), registry.decimals(&from_token, network).unwrap()) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        println!("Invalid amount. Please try again.");
+                        continue;
+                    }
+                };
 
-   //Generate mock data for the exchanges
-    pub fn mock_swap_data() -> Vec<Self> {
+                println!("Enter your slippage tolerance in % (press enter for {}%):", DEFAULT_SLIPPAGE_TOLERANCE * 100.0);
+                let mut slippage_input = String::new();
+                io::stdin().read_line(&mut slippage_input).expect("Failed to read line");
+                let max_slippage = match slippage_input.trim() {
+                    "" => DEFAULT_SLIPPAGE_TOLERANCE,
+                    input => match input.parse::<f64>() {
+                        Ok(val) if (0.0..100.0).contains(&val) => val / 100.0,
+                        _ => {
+                            println!("Invalid slippage tolerance. Please try again.");
+                            continue;
+                        }
+                    },
+                };
 
-       let uniswap_pairs = vec![
+                // Now use the perform_swap function
+                let swap = Swap {
+                    from_token: Token {
+                        token_type: from_token,
+                    },
+                    to_token: Token {
+                        token_type: to_token,
+                    },
+                    amount,
+                    max_slippage,
+                    user_wallet_address: wallet_address.to_string()
+                };
 
-           TokenPairData {
-           
-               from_token: TokenType::USDT,
-               to_token: TokenType::USDC,
-               swap_rate: 1.001,
-               liquidity: 50_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDT,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0005,
-               liquidity: 45_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::USDT,
-               swap_rate: 0.999,
-               liquidity: 49_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0002,
-               liquidity: 40_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDT,
-               swap_rate: 1.0003,
-               liquidity: 44_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDC,
-               swap_rate: 0.9998,
-               liquidity: 39_000_000,
-           }
-       ];
-
-       //token pairs data for Carbon exchange
-       let carbon_pairs = vec![
-           TokenPairData {
-           
-               from_token: TokenType::USDT,
-               to_token: TokenType::USDC,
-               swap_rate: 1.002,
-               liquidity: 48_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDT,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.001,
-               liquidity: 42_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::USDT,
-               swap_rate: 0.998,
-               liquidity: 47_500_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0015,
-               liquidity: 38_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDT,
-               swap_rate: 1.0008,
-               liquidity: 43_500_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDC,
-               swap_rate: 0.9995,
-               liquidity: 37_500_000,
-           }
-       ];
-
-       //token pairs for CowSwap exchange
-       let cowswap_pairs = vec![
-       
-           TokenPairData {
-           
-               from_token: TokenType::USDT,
-               to_token: TokenType::USDC,
-               swap_rate: 1.003,
-               liquidity: 36_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDT,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0005,
-               liquidity: 32_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::USDT,
-               swap_rate: 0.997,
-               liquidity: 34_500_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0013,
-               liquidity: 30_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDT,
-               swap_rate: 1.0009,
-               liquidity: 33_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDC,
-               swap_rate: 0.9997,
-               liquidity: 29_000_000,
-           }
-       ];
-
-
-       //token pairs for Matcha Exchange
-       let matcha_pairs = vec![
-       
-           TokenPairData {
-           
-               from_token: TokenType::USDT,
-               to_token: TokenType::USDC,
-               swap_rate: 1.004,
-               liquidity: 42_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDT,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0006,
-               liquidity: 37_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::USDT,
-               swap_rate: 0.996,
-               liquidity: 40_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::USDC,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0011,
-               liquidity: 35_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDT,
-               swap_rate: 1.0010,
-               liquidity: 38_000_000,
-           },
-
-           TokenPairData {
-
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDC,
-               swap_rate: 0.9995,
-               liquidity: 36_500_000,
-           }
-       ];
-
-
-       //token pairs for Sushi exchange
-       let sushi_pairs = vec![
-       
-           TokenPairData {
-           
-               from_token: TokenType::USDT,
-               to_token: TokenType::USDC,
-               swap_rate: 1.005,
-               liquidity: 48_000_000,
-           },
+                match perform_swap(&swap, venues, aggregators, gas, user_db) {
+                    Ok(result) => print_swap_result(&result, &swap.to_token.token_type),
+                    Err(error) => {
+                        println!("Swap failed: {}", error);
+                    }
+                }
+            },
+            "3" => {
+                // Bridge tokens from the current network to another one
+                let token = select_token("Choose the token you want to bridge:", registry, network);
+                let destinations = bridge.destinations(&token, network);
 
-           TokenPairData {
+                if destinations.is_empty() {
+                    println!("{} can't be bridged from {:?}.", token, network);
+                    continue;
+                }
 
-               from_token: TokenType::USDT,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0007,
-               liquidity: 44_000_000,
-           },
+                println!("Choose the network you want to bridge to:");
+                for (index, destination) in destinations.iter().enumerate() {
+                    println!("{}. {:?}", index + 1, destination);
+                }
 
-           TokenPairData {
+                let mut destination_input = String::new();
+                io::stdin().read_line(&mut destination_input).expect("Failed to read line");
+                let to_network = match destination_input.trim().parse::<usize>() {
+                    Ok(index) if index >= 1 && index <= destinations.len() => destinations[index - 1],
+                    _ => {
+                        println!("Invalid selection");
+                        continue;
+                    }
+                };
 
-               from_token: TokenType::USDC,
-               to_token: TokenType::USDT,
-               swap_rate: 0.995,
-               liquidity: 46_000_000,
-           },
+                println!("Enter the amount you want to bridge:");
+                let mut amount_input = String::new();
+                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
+                let amount = match TokenAmount::parse(amount_input.trim(), registry.decimals(&token, network).unwrap()) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        println!("Invalid amount. Please try again.");
+                        continue;
+                    }
+                };
 
-           TokenPairData {
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
 
-               from_token: TokenType::USDC,
-               to_token: TokenType::BUSD,
-               swap_rate: 1.0010,
-               liquidity: 43_000_000,
-           },
+                match bridge.transfer(user, &token, to_network, amount) {
+                    Ok(transfer) => {
+                        println!("Bridge transfer sent! Fee: {} {}, {} {} arrives on {:?} at block {} (now {})",
+                            transfer.fee, token, transfer.received_amount, token, to_network,
+                            transfer.arrival_block, bridge.current_block());
+                    },
+                    Err(error) => {
+                        println!("Bridge failed: {}", error);
+                    }
+                }
+            },
+            "4" => {
+                // Switch network, balances on the other networks stay where they are
+                let Some(network) = select_network() else {
+                    println!("Invalid selection");
+                    continue;
+                };
 
-           TokenPairData {
+                user_db.get_user_by_address_mut(wallet_address).unwrap().switch_network(network);
+                println!("Switched to {:?}.", network);
+            },
+            "5" => {
+                // Let the chain move on so pending bridge transfers arrive and open batches settle
+                println!("Enter the number of blocks to wait:");
+                let mut blocks_input = String::new();
+                io::stdin().read_line(&mut blocks_input).expect("Failed to read line");
+
+                match blocks_input.trim().parse::<u64>() {
+                    Ok(blocks) => {
+                        bridge.advance_blocks(blocks);
+                        println!("Now at block {}.", bridge.current_block());
+                    },
+                    Err(_) => println!("Invalid number of blocks."),
+                }
+            },
+            "6" => {
+                // Fees every exchange on the user's network has collected so far
+                let fees: Vec<_> = venues.iter()
+                    .filter(|venue| venue.network() == network)
+                    .flat_map(|venue| venue.collected_fees())
+                    .collect();
+
+                let aggregator_fees: Vec<_> = aggregators.iter()
+                    .filter(|aggregator| aggregator.network == network)
+                    .flat_map(|aggregator| aggregator.collected_fees.iter().map(move |(token, fee)| (aggregator.name(), token, fee)))
+                    .collect();
+
+                if fees.is_empty() && aggregator_fees.is_empty() {
+                    println!("No fees collected on {:?} yet.", network);
+                }
 
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDT,
-               swap_rate: 1.0015,
-               liquidity: 45_000_000,
-           },
+                for fee in fees {
+                    println!("{}: {} {} to LPs, {} {} protocol fees", fee.venue_name, fee.lp_fee, fee.token, fee.protocol_fee, fee.token);
+                }
 
-           TokenPairData {
+                for (name, token, fee) in aggregator_fees {
+                    println!("{}: {} {} aggregator fees", name, fee, token);
+                }
+            },
+            "7" => {
+                // Add liquidity to one of the pools on the user's network
+                let pools: Vec<(usize, String, TokenType, TokenType)> = venues.iter().enumerate()
+                    .filter(|(_, venue)| venue.network() == network)
+                    .flat_map(|(index, venue)| {
+                        venue.supported_pairs().into_iter().map(move |(from, to)| (index, venue.name().to_string(), from, to))
+                    })
+                    .collect();
+
+                println!("Choose the pool you want to add liquidity to:");
+                for (number, (_, name, from, to)) in pools.iter().enumerate() {
+                    println!("{}. {} {} -> {}", number + 1, name, from, to);
+                }
 
-               from_token: TokenType::BUSD,
-               to_token: TokenType::USDC,
-               swap_rate: 0.9998,
-               liquidity: 42_500_000,
-           }
-       ];
+                let mut pool_input = String::new();
+                io::stdin().read_line(&mut pool_input).expect("Failed to read line");
+                let (venue_index, _, from, to) = match pool_input.trim().parse::<usize>() {
+                    Ok(number) if number >= 1 && number <= pools.len() => pools[number - 1].clone(),
+                    _ => {
+                        println!("Invalid selection");
+                        continue;
+                    }
+                };
 
-        vec![
+                // concentrated pools take liquidity in a price range instead
+                if let Some(price) = venues[venue_index].range_price(&from, &to) {
+
+                    println!("The pool price is {:.6} {} per {}. Enter the lower and upper price of your range, e.g. 0.99 1.01:", price, to, from);
+                    let mut range_input = String::new();
+                    io::stdin().read_line(&mut range_input).expect("Failed to read line");
+                    let prices: Vec<f64> = range_input.split_whitespace().filter_map(|price| price.parse().ok()).collect();
+                    let (lower_price, upper_price) = match prices[..] {
+                        [lower, upper] => (lower, upper),
+                        _ => {
+                            println!("Invalid range. Please try again.");
+                            continue;
+                        }
+                    };
+
+                    println!("Enter the most {} and {} the range may take, e.g. 1000 1000:", from, to);
+                    let mut amounts_input = String::new();
+                    io::stdin().read_line(&mut amounts_input).expect("Failed to read line");
+                    let amounts: Vec<&str> = amounts_input.split_whitespace().collect();
+                    let (from_amount, to_amount) = match amounts[..] {
+                        [from_amount, to_amount] => match (
+                            TokenAmount::parse(from_amount, registry.decimals(&from, network).unwrap()),
+                            TokenAmount::parse(to_amount, registry.decimals(&to, network).unwrap()),
+                        ) {
+                            (Ok(from_amount), Ok(to_amount)) => (from_amount, to_amount),
+                            _ => {
+                                println!("Invalid amount. Please try again.");
+                                continue;
+                            }
+                        },
+                        _ => {
+                            println!("Invalid amount. Please try again.");
+                            continue;
+                        }
+                    };
+
+                    let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
+                    let order = RangeOrder { lower_price, upper_price, from_amount, to_amount };
+
+                    match add_range_liquidity(venues, venue_index, &from, &to, &order, user) {
+                        Ok(deposit) => {
+                            println!("Liquidity added! Deposited {} {} + {} {} between {:.6} and {:.6}",
+                                deposit.from_amount, from, deposit.to_amount, to, deposit.lower_price, deposit.upper_price);
+                        },
+                        Err(error) => println!("Adding liquidity failed: {}", error),
+                    }
 
-            ExchangeData {
+                    continue;
+                }
 
-                exchange_name: Exchange::Uniswap,
-                supported_network: Network::Ethereum,
-                token_pairs: uniswap_pairs,
-                
-            },
+                println!("Enter the amount of {} to deposit, the matching {} is added for you:", from, to);
+                let mut amount_input = String::new();
+                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
+                let amount = match TokenAmount::parse(amount_input.trim(), registry.decimals(&from, network).unwrap()) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        println!("Invalid amount. Please try again.");
+                        continue;
+                    }
+                };
 
-            ExchangeData {
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
 
-                exchange_name: Exchange::Carbon,
-                supported_network: Network::Ethereum,
-                token_pairs: carbon_pairs,
+                match add_liquidity(venues, venue_index, &from, &to, amount, user) {
+                    Ok(deposit) => {
+                        println!("Liquidity added! Deposited {} {} + {} {} for {} shares",
+                            deposit.from_amount, from, deposit.to_amount, to, deposit.shares);
+                    },
+                    Err(error) => println!("Adding liquidity failed: {}", error),
+                }
             },
+            "8" => {
+                // Remove some or all of one of the user's LP positions
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
 
-            ExchangeData {
+                if user.lp_positions.is_empty() {
+                    println!("You have no liquidity positions.");
+                    continue;
+                }
 
-                exchange_name: Exchange::CowSwap,
-                supported_network: Network::Ethereum,
-                token_pairs: cowswap_pairs,
-            },
+                println!("Choose the position you want to withdraw from:");
+                for (number, position) in user.lp_positions.iter().enumerate() {
+                    match position.range {
+                        Some(range) => println!("{}. {} {} -> {} on {:?}, range {:.4}-{:.4}", number + 1, position.venue_name,
+                            position.from_token, position.to_token, position.network, range.lower_price, range.upper_price),
+                        None => println!("{}. {} {} -> {} on {:?}, {} shares", number + 1, position.venue_name,
+                            position.from_token, position.to_token, position.network, position.shares),
+                    }
+                }
 
-            ExchangeData {
+                let mut position_input = String::new();
+                io::stdin().read_line(&mut position_input).expect("Failed to read line");
+                let position_index = match position_input.trim().parse::<usize>() {
+                    Ok(number) if number >= 1 && number <= user.lp_positions.len() => number - 1,
+                    _ => {
+                        println!("Invalid selection");
+                        continue;
+                    }
+                };
 
-                exchange_name: Exchange::Matcha,
-                supported_network: Network::Ethereum,
-                token_pairs: matcha_pairs,
-            },
+                println!("Enter the percentage of the position to withdraw (100 for all):");
+                let mut percent_input = String::new();
+                io::stdin().read_line(&mut percent_input).expect("Failed to read line");
+                let shares = match percent_input.trim().parse::<u128>() {
+                    Ok(percent) if (1..=100).contains(&percent) => user.lp_positions[position_index].shares * percent / 100,
+                    _ => {
+                        println!("Invalid percentage.");
+                        continue;
+                    }
+                };
 
-            ExchangeData {
+                let position = user.lp_positions[position_index].clone();
 
-               exchange_name: Exchange::Sushi,
-               supported_network: Network::Ethereum,
-               token_pairs: sushi_pairs,
+                match remove_liquidity(venues, position_index, shares, user) {
+                    Ok(withdrawn) => {
+                        println!("Liquidity removed! Received {} {} and {} {} on {:?}",
+                            withdrawn.from_amount, position.from_token, withdrawn.to_amount, position.to_token, position.network);
+                    },
+                    Err(error) => println!("Removing liquidity failed: {}", error),
+                }
             },
+            "9" => {
+                // Place an order in the batch auction on the user's network
+                let Some(auction_index) = auctions.iter().position(|auction| auction.network == network) else {
+                    println!("There is no batch auction on {:?}.", network);
+                    continue;
+                };
 
-            
-        ]
-
-    }
-
-}
-
+                let from_token = select_token("Choose the token you want to sell:", registry, network);
+                let to_token = select_token("Choose the token you want to buy:", registry, network);
 
+                if from_token == to_token {
+                    println!("Both source and destination tokens are the same. Please try again.");
+                    continue;
+                }
 
-//Implementing user logic
+                println!("Enter the amount you want to sell:");
+                let mut amount_input = String::new();
+                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
+                let amount = match TokenAmount::parse(amount_input.trim(), registry.decimals(&from_token, network).unwrap()) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        println!("Invalid amount. Please try again.");
+                        continue;
+                    }
+                };
 
-impl User {
+                println!("Enter your slippage tolerance in % (press enter for {}%):", DEFAULT_SLIPPAGE_TOLERANCE * 100.0);
+                let mut slippage_input = String::new();
+                io::stdin().read_line(&mut slippage_input).expect("Failed to read line");
+                let max_slippage = match slippage_input.trim() {
+                    "" => DEFAULT_SLIPPAGE_TOLERANCE,
+                    input => match input.parse::<f64>() {
+                        Ok(val) if (0.0..100.0).contains(&val) => val / 100.0,
+                        _ => {
+                            println!("Invalid slippage tolerance. Please try again.");
+                            continue;
+                        }
+                    },
+                };
 
-    //create a new user with random balances
-    pub fn new(network: Network, wallet_address: String) -> Self {
+                // the order's limit is what swapping directly pays after gas now, less the tolerance
+                let direct = match Router::find_best_route(venues, gas, network, &from_token, &to_token, amount) {
+                    Ok(route) => {
+                        let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+                        let received = route.last().unwrap().quote.received_amount;
+                        gas.net_output(network, gas_units, &to_token, received).unwrap_or(received)
+                    },
+                    Err(error) => {
+                        println!("No price for this pair: {}", error);
+                        continue;
+                    }
+                };
+                // a tolerance that rounds to 100% would take any price at all
+                let tolerance_bps = (max_slippage * 10_000.0).round() as u128;
+                let min_received = match 10_000u128.checked_sub(tolerance_bps).filter(|&kept_bps| kept_bps > 0) {
+                    Some(kept_bps) => match direct.mul_div(kept_bps, 10_000) {
+                        Ok(min_received) => min_received,
+                        Err(error) => {
+                            println!("No limit for this order: {}", error);
+                            continue;
+                        }
+                    },
+                    None => {
+                        println!("Invalid slippage tolerance. Please try again.");
+                        continue;
+                    }
+                };
 
-        let mut balances = HashMap::new();
-    
+                let current_block = bridge.current_block();
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
+                let auction = &mut auctions[auction_index];
 
-    
-        // Generate random balances for each token
-        for token in [TokenType::USDC, TokenType::USDT, TokenType::BUSD].iter() {
+                match auction.place_order(user, &from_token, &to_token, amount, min_received, current_block) {
+                    Ok(_) => {
+                        println!("Order placed with {}! It settles at block {} (now {}), for at least {} {}",
+                            auction.exchange_name.as_str(), auction.closes_at().unwrap(), current_block, min_received, to_token);
+                    },
+                    Err(error) => println!("Placing the order failed: {}", error),
+                }
+            },
+            "10" => {
+                // Place a maker strategy on an exchange that takes them
+                let Some(venue_index) = venues.iter().position(|venue| venue.network() == network && venue.takes_strategies()) else {
+                    println!("No exchange on {:?} takes maker strategies.", network);
+                    continue;
+                };
 
-            balances.insert(*token, (rand::random::<u64>() % 10_000) as f64); // Randomly assigns 0 to 9999 tokens
-        }
+                let base = select_token("Choose the token your strategy sells and buys back (base):", registry, network);
+                let quote = select_token("Choose the token you price it in (quote):", registry, network);
 
-        User {
+                if base == quote {
+                    println!("Both tokens are the same. Please try again.");
+                    continue;
+                }
 
-            network,
-            wallet_address,
-            balances
-        }
-    }
+                let sell_prompt = format!("Enter the {} to sell and the range to sell it in, {} per {}, e.g. 1000 1.001 1.003:", base, quote, base);
+                let Some((sell_amount, sell_low, sell_high)) = read_strategy_order(&sell_prompt, registry.decimals(&base, network).unwrap()) else {
+                    println!("Invalid order. Please try again.");
+                    continue;
+                };
 
-    // Function to check if user has sufficient balance of a given token
-    pub fn has_sufficient_balance(&self, token: TokenType, amount: f64) -> bool {
+                let buy_prompt = format!("Enter the {} to buy {} with and the range to buy in, e.g. 1000 0.997 0.999:", quote, base);
+                let Some((buy_amount, buy_low, buy_high)) = read_strategy_order(&buy_prompt, registry.decimals(&quote, network).unwrap()) else {
+                    println!("Invalid order. Please try again.");
+                    continue;
+                };
 
+                let params = StrategyParams { base, quote, sell_amount, sell_low, sell_high, buy_amount, buy_low, buy_high };
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
 
-        match self.balances.get(&token) {
+                match place_strategy(venues, venue_index, &params, user) {
+                    Ok(strategy) => println!("Strategy #{} placed on {}! Takers swapping through it fill your orders.", strategy.id, venues[venue_index].name()),
+                    Err(error) => println!("Placing the strategy failed: {}", error),
+                }
+            },
+            "11" => {
+                // Withdraw one of the user's strategies with what its orders hold now
+                let strategies: Vec<(usize, Strategy)> = venues.iter().enumerate()
+                    .filter(|(_, venue)| venue.network() == network)
+                    .flat_map(|(index, venue)| venue.strategies().iter().filter(|strategy| strategy.owner == wallet_address).map(move |strategy| (index, strategy.clone())))
+                    .collect();
+
+                if strategies.is_empty() {
+                    println!("You have no strategies on {:?}.", network);
+                    continue;
+                }
 
-            Some(balance) => *balance >= amount,
-            None => false,
-        }
-    }
+                println!("Choose the strategy you want to withdraw:");
+                for (number, (venue_index, strategy)) in strategies.iter().enumerate() {
+                    println!("{}. #{} on {}, {} {} + {} {}", number + 1, strategy.id, venues[*venue_index].name(),
+                        strategy.sell.amount, strategy.base, strategy.buy.amount, strategy.quote);
+                }
 
+                let mut strategy_input = String::new();
+                io::stdin().read_line(&mut strategy_input).expect("Failed to read line");
+                let (venue_index, strategy_id) = match strategy_input.trim().parse::<usize>() {
+                    Ok(number) if number >= 1 && number <= strategies.len() => (strategies[number - 1].0, strategies[number - 1].1.id),
+                    _ => {
+                        println!("Invalid selection");
+                        continue;
+                    }
+                };
 
-    // Deduct the specified amount from the user's balance for a given token
-    pub fn deduct_balance(&mut self, token: TokenType, amount: f64) -> Result<(), SwapError> {
+                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
 
-        if self.has_sufficient_balance(token, amount) {
+                match withdraw_strategy(venues, venue_index, strategy_id, user) {
+                    Ok(strategy) => println!("Strategy #{} withdrawn! Received {} {} and {} {}",
+                        strategy.id, strategy.sell.amount, strategy.base, strategy.buy.amount, strategy.quote),
+                    Err(error) => println!("Withdrawing the strategy failed: {}", error),
+                }
+            },
+            "12" => {
+                // Every exchange's and aggregator's price for a pair, nothing is executed
+                let from_token = select_token("Choose the token you want to swap FROM:", registry, network);
+                let to_token = select_token("Choose the token you want to swap TO:", registry, network);
 
-            if let Some(balance) = self.balances.get_mut(&token) {
+                if from_token == to_token {
+                    println!("Both source and destination tokens are the same. Please try again.");
+                    continue;
+                }
 
-                *balance -= amount;
-                Ok(())
-            } else {
+                println!("Enter the amount you want to quote:");
+                let mut amount_input = String::new();
+                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
+                let amount = match TokenAmount::parse(amount_input.trim(), registry.decimals(&from_token, network).unwrap()) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        println!("Invalid amount. Please try again.");
+                        continue;
+                    }
+                };
 
-                Err(SwapError::BalanceNotFound)
+                match compare_quotes(venues, aggregators, gas, network, &from_token, &to_token, amount) {
+                    Ok(quotes) => {
+                        println!("Quotes for {} {} -> {} on {:?}:", amount, from_token, to_token, network);
+                        print_quote_table(&quotes);
+                    },
+                    Err(error) => println!("Quote failed: {}", error),
+                }
+            },
+            "13" => {
+                save_users(user_db, venues, bridge, auctions);
+                break;
+            },
+            _ => {
+                println!("Invalid choice.");
             }
-        } else {
-
-            Err(SwapError::InsufficientBalance)
         }
     }
-
-
-    // Add the specified amount to the user's balance for a given token
-    pub fn add_balance(&mut self, token: TokenType, amount: f64) {
-
-        *self.balances.entry(token).or_insert(0.0) += amount;
-    }    
 }
 
-//Managing multiple users
-
-impl UserDatabase {
 
-    //initialize a new empty db
+//Reads one order of a strategy as "amount low high", the amount may be 0 to leave that
+//side empty
+fn read_strategy_order(prompt: &str, decimals: u8) -> Option<(TokenAmount, f64, f64)> {
+    println!("{}", prompt);
+    let mut order_input = String::new();
+    io::stdin().read_line(&mut order_input).expect("Failed to read line");
 
-    pub fn new_db()  -> Self{
-
-        UserDatabase {users: Vec::new()}
+    match order_input.split_whitespace().collect::<Vec<_>>()[..] {
+        [amount, low, high] => Some((TokenAmount::parse(amount, decimals).ok()?, low.parse().ok()?, high.parse().ok()?)),
+        _ => None,
     }
+}
 
-    //Add new user to the db
 
-    pub fn add_user(&mut self, user: User) {
+//This function is for selecting the network a user swaps on
+fn select_network() -> Option<Network> {
+    println!("Please select a network (1. Ethereum, 2. BNBChain, 3. Polygon):");
+    let mut network_input = String::new();
+    io::stdin().read_line(&mut network_input).expect("Failed to read line");
 
-        self.users.push(user);
+    match network_input.trim() {
+        "1" => Some(Network::Ethereum),
+        "2" => Some(Network::BNBChain),
+        "3" => Some(Network::Polygon),
+        _ => None,
     }
+}
 
-    //fetch a user in db. Returns Option as user might not be in db
 
-    pub fn get_user_by_address(&mut self, address: &str) -> Option<&mut User> {
+//what an executed swap paid and how it was routed
+fn print_swap_result(result: &SwapResult, to_token: &TokenType) {
 
-        self.users.iter_mut().find(|user| &user.wallet_address == address)
+    match &result.aggregator {
+        Some(aggregator) => println!("Swap Successful through {}! Received amount: {}", aggregator, result.received_amount),
+        None => println!("Swap Successful! Received amount: {}", result.received_amount),
     }
 
-    // Fetch a mutable reference to a user by wallet address. This allows you to update the user's details.
+    print_options(&result.options, to_token);
+    print_legs(&result.legs);
 
-    pub fn get_user_by_address_mut(&mut self, address: &str) -> Option<&mut User> {
+    println!("Spot price: {:.6}, execution price: {:.6}, price impact: {:.2} bps, slippage: {:.4}%",
+        result.spot_price, result.execution_price, result.price_impact_bps, result.slippage * 100.0);
+    println!("Gas: {} units, about ${:.2} ({} {})",
+        result.gas_units, result.gas_cost_usd, result.gas_cost, to_token);
 
-        self.users.iter_mut().find(|user| user.wallet_address == address)
+    for fee in &result.fees {
+        println!("  Fees on {}: {} {} to LPs, {} {} protocol fee",
+            fee.venue_name, fee.lp_fee, fee.token, fee.protocol_fee, fee.token);
     }
+}
 
-    // Remove a user from database
-    
-    pub fn remove_user_by_address(&mut self, address: &str) {
-
-        if let Some(index) = self.users.iter().position(|user| user.wallet_address == address) {
+//going direct against going through each aggregator, as quoted
+fn print_options(options: &[SwapOption], to_token: &TokenType) {
 
-            self.users.remove(index);
+    if options.len() > 1 {
+        for option in options {
+            println!("  Quoted {}: {} {} after gas", option.name, option.net_amount, to_token);
         }
     }
-
 }
 
+//every leg of an order and the hops it goes through
+fn print_legs(legs: &[SwapLeg]) {
 
-//Logic for performing a swap
-fn perform_swap(swap: &Swap, exchanges: &[ExchangeData], user_db: &mut UserDatabase) -> Result<SwapResult, SwapError> {
-    // 1. Fetch the user
-    let user = match user_db.get_user_by_address_mut(&swap.user_wallet_address) {
-        Some(u) => u,
-        None => return Err(SwapError::UserNotFound),
-    };
+    for leg in legs {
+        let route: Vec<String> = leg.route.iter()
+            .map(|hop| format!("{} ({} -> {})", hop.venue_name, hop.from_token, hop.to_token))
+            .collect();
 
-    // 2. Ensure the user has enough balance for the swap
-    if !user.has_sufficient_balance(swap.from_token.token_type, swap.amount) {
-        return Err(SwapError::InsufficientBalance);
-    }
-
-    let mut best_result: Option<SwapResult> = None;
-
-    // 3. Loop through each exchange
-    for exchange in exchanges {
-        let result = exchange.simulate_swap(swap.from_token.token_type, swap.to_token.token_type, swap.amount);
-        
-        match result {
-            Ok(received_amount) => {
-                if best_result.is_none() || received_amount > best_result.as_ref().unwrap().received_amount {
-                    best_result = Some(SwapResult {
-                        exchange_name: exchange.exchange_name,
-                        received_amount,
-                        slippage: 0.01, // Assuming a fixed slippage of 1% for now
-                    });
-                }
-            },
-            Err(_) => continue,
-        }
-    }
-
-    // 4. If a suitable exchange is found, proceed with the swap
-    if let Some(best_swap) = best_result {
-        user.deduct_balance(swap.from_token.token_type, swap.amount)?;
-        user.add_balance(swap.to_token.token_type, best_swap.received_amount);
-        Ok(best_swap)
-    } else {
-        Err(SwapError::TokenPairNotSupported)
+        println!("  {} in via {} -> {} out", leg.amount_in, route.join(", "), leg.quote.received_amount);
     }
 }
 
-// `user_menu` provides an interactive interface to the user
-//The function allows the user to interact with their account by providing
-//multiple options, such as viewing balances or initiating a swap transaction.
-fn user_menu(wallet_address: &str, exchanges: &[ExchangeData], user_db: &mut UserDatabase) {
-    
-    loop {
-        println!("--- User Menu ---");
-        println!("1. View balances");
-        println!("2. Initiate swap");
-        println!("3. Exit");
-        println!("Select an option:");
-        println!();
-
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice).expect("Failed to read line");
 
-        match choice.trim() {
-            "1" => {
-               
-                let user = user_db.get_user_by_address_mut(wallet_address).unwrap();
-                
-                // View balances
-                println!("Your balances are: {:?}", user.balances);
-            },
-            "2" => {
-                // Initiate swap 
-                let from_token = select_token("Choose the token you want to swap FROM:");
-                let to_token = select_token("Choose the token you want to swap TO:");
-
-                //incase user chooses the same token to swap.
-                if from_token == to_token {
-                    println!("Both source and destination tokens are the same. Please try again.");
-                    continue;
-                }
+//Quotes ranked best first, one row per exchange or aggregator. The ones that can't do the
+//swap are listed last with the reason.
+fn print_quote_table(quotes: &[VenueQuote]) {
 
-                println!("Enter the amount you want to swap:");
-                let mut amount_input = String::new();
-                io::stdin().read_line(&mut amount_input).expect("Failed to read line");
-                let amount: f64 = match amount_input.trim().parse() {
-                    Ok(val) => val,
-                    Err(_) => {
-                        println!("Invalid amount. Please try again.");
-                        continue;
-                    }
-                };
+    println!("{:<4}{:<14}  {:>28}  {:>12}  {:>28}  {:>28}", "#", "Exchange", "Received", "Rate", "After gas", "Liquidity");
 
-                // Now use the perform_swap function
-                let swap = Swap {
-                    from_token: Token {
-                        token_type: from_token,
-                    },
-                    to_token: Token {
-                        token_type: to_token,
-                    },
-                    amount,
-                    user_wallet_address: wallet_address.to_string()
-                };
+    for (rank, venue_quote) in quotes.iter().enumerate() {
+        let liquidity = venue_quote.liquidity.map_or("-".to_string(), |liquidity| liquidity.to_string());
 
-                match perform_swap(&swap, exchanges, user_db) {
-                    Ok(result) => {
-                        println!("Swap Successful! Best exchange: {:?}. Received amount: {} with slippage of {}%",
-                            result.exchange_name, result.received_amount, result.slippage * 100.0);
-                    },
-                    Err(error) => {
-                        println!("Swap failed: {:?}", error);
-                    }
-                }
-            },
-            "3" => {
-                break;
-            },
-            _ => {
-                println!("Invalid choice.");
-            }
+        match (&venue_quote.quote, venue_quote.net_amount) {
+            (Ok(quote), Some(net_amount)) => println!("{:<4}{:<14}  {:>28}  {:>12.6}  {:>28}  {:>28}",
+                rank + 1, venue_quote.venue_name, quote.received_amount.to_string(), quote.execution_price, net_amount.to_string(), liquidity),
+            (Err(error), _) => println!("{:<4}{:<14}  {:>28}  {:>12}  {:>28}  {:>28}",
+                "-", venue_quote.venue_name, error.to_string(), "-", "-", liquidity),
+            _ => {},
         }
     }
 }
 
+//This function is for selecting the token you want to swap FROM and TO,
+//out of the tokens registered on the user's network
+fn select_token(prompt: &str, registry: &TokenRegistry, network: Network) -> TokenType {
+    let tokens = registry.tokens_on(network);
 
-//This function is for selecting the token you want to swap FROM and TO
-fn select_token(prompt: &str) -> TokenType {
     loop {
         println!("{}", prompt);
-        println!("1. USDC");
-        println!("2. USDT");
-        println!("3. BUSD");
+        for (index, info) in tokens.iter().enumerate() {
+            println!("{}. {} ({})", index + 1, info.symbol, info.name);
+        }
         println!();
 
 
         let mut choice = String::new();
         io::stdin().read_line(&mut choice).expect("Failed to read line");
 
-        match choice.trim() {
-            "1" => return TokenType::USDC,
-            "2" => return TokenType::USDT,
-            "3" => return TokenType::BUSD,
+        match choice.trim().parse::<usize>() {
+            Ok(index) if index >= 1 && index <= tokens.len() => return tokens[index - 1].symbol.clone(),
             _ => println!("Invalid choice. Please try again.")
         }
     }
@@ -713,8 +754,65 @@ fn select_token(prompt: &str) -> TokenType {
 
 
 
+//take `name <value>` out of the arguments, a name without a value is a usage error
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+
+    let index = args.iter().position(|arg| arg == name)?;
+
+    let Some(value) = args.get(index + 1).cloned() else {
+        eprintln!("{}", cli::USAGE);
+        process::exit(cli::EXIT_USAGE);
+    };
+    args.drain(index..index + 2);
+
+    Some(value)
+}
+
+//Write the users to the `--db` file with the bridge's clock, the session goes on if that fails.
+//What they hold in the pools, strategies and batches is saved as paid back to them.
+fn save_users(user_db: &mut UserDatabase, venues: &[Box<dyn Venue>], bridge: &Bridge, auctions: &[BatchAuction]) {
+
+    user_db.block = bridge.current_block();
+
+    if let Err(error) = user_db.save_with_market(venues, auctions) {
+        println!("Saving users failed: {}", error);
+    }
+}
+
+
 fn main() {
 
+    // Tokens and exchanges come from the file passed with `--config`, or the default exchanges.toml.
+    // Users are kept in the file passed with `--db`, or only in memory for this run.
+    // Whatever is left is a subcommand for scripts, without one the interactive menu starts.
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let config = match take_option(&mut args, "--config") {
+        Some(path) => match config::load_config(Path::new(&path)) {
+            Ok(config) => config,
+            Err(error) => {
+                eprintln!("Error in {}: {}", path, error);
+                process::exit(1);
+            }
+        },
+        None => config::load_default(),
+    };
+
+    let mut user_db = match take_option(&mut args, "--db") {
+        Some(path) => match UserDatabase::open(Path::new(&path), &config.tokens) {
+            Ok(user_db) => user_db,
+            Err(error) => {
+                eprintln!("Error in {}: {}", path, error);
+                process::exit(1);
+            }
+        },
+        None => UserDatabase::new_db(),
+    };
+
+    if let Some(command) = args.first() {
+        process::exit(cli::run(command, &args[1..], config, &mut user_db));
+    }
+
     println!(r#"
 
  
@@ -745,9 +843,13 @@ fn main() {
               🦀 Built with Love in Rust 🦀
     "#);
     
-    let exchanges = ExchangeData::mock_swap_data();
-    let mut user_db = UserDatabase::new_db();
-
+    let registry = config.tokens;
+    let mut market = Market {
+        venues: ExchangeData::into_venues(config.exchanges),
+        bridge: Bridge::starting_at(config.bridges, user_db.block),
+        auctions: config.auctions,
+        aggregators: config.aggregators,
+    };
     loop {
         // Ask the user for their wallet address
         println!("Please enter your wallet address (or type 'exit' to quit):");
@@ -762,38 +864,21 @@ fn main() {
         let user_exists = user_db.get_user_by_address(wallet_address.trim()).is_some();
 
         if user_exists {
-            user_menu(wallet_address.trim(), &exchanges, &mut user_db);
+            user_menu(wallet_address.trim(), &registry, &mut market, &config.gas, &mut user_db);
         } else {
             // If its a New user, ask for their network
-            println!("Please select a network (1. Ethereum, 2. BNBChain, 3. Polygon):");
-            let mut network_input = String::new();
-            io::stdin().read_line(&mut network_input).expect("Failed to read line");
-            let network = match network_input.trim() {
-                "1" => Network::Ethereum,
-                "2" => Network::BNBChain,
-                "3" => Network::Polygon,
-                _ => {
-                    println!("Invalid selection");
-                    continue;
-                }
-            };
-
-            // Check if the network is Ethereum. If user chooses another network they get an error.
-            if network != Network::Ethereum {
-                println!("Error: {:?}", SwapError::IncorrectNetwork);
+            let Some(network) = select_network() else {
+                println!("Invalid selection");
                 continue;
-            }
+            };
 
             // Create new user with random balances and add to user_db
-            let new_user = User::new(network, wallet_address.trim().to_string());
+            let new_user = User::new(network, wallet_address.trim().to_string(), &registry);
             user_db.add_user(new_user);
+            save_users(&mut user_db, &market.venues, &market.bridge, &market.auctions);
             println!("User created with random balances!");
 
-            user_menu(wallet_address.trim(), &exchanges, &mut user_db);
+            user_menu(wallet_address.trim(), &registry, &mut market, &config.gas, &mut user_db);
         }
     }
 }
-
-
-
-
@@ -0,0 +1,90 @@
+use crate::concentrated::ConcentratedPool;
+use crate::token::TokenAmount;
+
+//how a pool prices its swaps
+#[derive(Debug, PartialEq, Clone)]
+pub enum PricingModel {
+
+    FixedRate(f64),                      //flat swap rate no matter the trade size
+    ConstantProduct,                     //x * y = k on the pool reserves
+    StableSwap { amplification: f64 },   //Curve-style invariant, flat near the peg and steep when imbalanced
+    Concentrated(ConcentratedPool),      //Uniswap v3 style, liquidity sits in price ranges between ticks
+}
+
+//What a pool would pay out for a swap, worked out before anything is executed.
+//Prices are after fees. Quotes combined over several pools leave the fees at zero,
+//every pool's own fees are in the quote of its RouteHop.
+#[derive(Debug, Clone, Copy)]
+pub struct SwapQuote {
+
+    pub received_amount: TokenAmount,
+    pub spot_price: f64,        //marginal price (to_token per from_token) before the swap
+    pub execution_price: f64,   //average price actually paid, received_amount / amount
+    pub price_after: f64,       //marginal price left in the pool once the swap went through
+    pub lp_fee: TokenAmount,         //fee left in the pool for its liquidity providers, in the token swapped in
+    pub protocol_fee: TokenAmount,   //fee taken out by the exchange, in the token swapped in
+}
+
+impl SwapQuote {
+
+    //how far the pool price moved, in basis points
+    pub fn price_impact_bps(&self) -> f64 {
+
+        (self.spot_price - self.price_after) / self.spot_price * 10_000.0
+    }
+
+    //difference between the quoted spot price and the price the swap executed at
+    pub fn slippage(&self) -> f64 {
+
+        (self.spot_price - self.execution_price) / self.spot_price
+    }
+}
+
+// StableSwap maths for a two token pool (n = 2), see the Curve whitepaper.
+// Both functions use Newton's method, 255 rounds is what Curve uses on-chain as well.
+
+//solves A*n^n*(x + y) + D = A*D*n^n + D^(n+1) / (n^n*x*y) for the invariant D
+pub(crate) fn stableswap_invariant(x: f64, y: f64, amplification: f64) -> f64 {
+
+    let sum = x + y;
+    if sum == 0.0 {
+        return 0.0;
+    }
+
+    let ann = amplification * 4.0;
+    let mut d = sum;
+
+    for _ in 0..255 {
+
+        let d_p = d * d * d / (4.0 * x * y);
+        let prev_d = d;
+        d = (ann * sum + 2.0 * d_p) * d / ((ann - 1.0) * d + 3.0 * d_p);
+
+        if (d - prev_d).abs() <= d * f64::EPSILON {
+            break;
+        }
+    }
+
+    d
+}
+
+//balance of the other token that keeps the invariant D once one side holds `x`
+pub(crate) fn stableswap_balance(x: f64, d: f64, amplification: f64) -> f64 {
+
+    let ann = amplification * 4.0;
+    let c = d * d * d / (4.0 * x * ann);
+    let b = x + d / ann;
+    let mut y = d;
+
+    for _ in 0..255 {
+
+        let prev_y = y;
+        y = (y * y + c) / (2.0 * y + b - d);
+
+        if (y - prev_y).abs() <= y * f64::EPSILON {
+            break;
+        }
+    }
+
+    y
+}
@@ -0,0 +1,518 @@
+use serde::Serialize;
+
+use crate::aggregator::Aggregator;
+use crate::exchange::Network;
+use crate::gas::GasModel;
+use crate::router::SwapLeg;
+use crate::swap::{SwapOption, SwapPlan, SwapResult, VenueQuote};
+use crate::token::{TokenAmount, TokenRegistry, TokenType};
+use crate::user::User;
+
+// Serializable representations of what the tool prints, for dashboards and scripts.
+// Amounts are decimal strings so they keep every base unit, prices and gas are numbers.
+
+//how results are written out, `--format` on the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+
+    Table,   //the same text the menu prints
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+
+        match name {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+//a report can be written as JSON or CSV, the CSV has a header line
+pub trait Report: Serialize {
+
+    fn csv(&self) -> String;
+
+    fn json(&self) -> String {
+
+        serde_json::to_string_pretty(self).expect("reports only hold strings and numbers")
+    }
+}
+
+//an executed swap, or the best way to do one when it was only quoted
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapReport {
+
+    pub network: String,
+    pub from_token: String,
+    pub to_token: String,
+    pub amount_in: String,
+    pub received_amount: String,
+    pub aggregator: Option<String>,   //None if it went direct
+    pub spot_price: f64,
+    pub execution_price: f64,
+    pub price_impact_bps: f64,
+    pub slippage: f64,
+    pub gas_units: u64,
+    pub gas_cost: String,             //in to_token
+    pub gas_cost_usd: f64,
+    pub legs: Vec<LegReport>,
+    pub fees: Vec<FeeReport>,         //every hop's fees and the aggregator's, as quoted until the swap is executed
+    pub options: Vec<OptionReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegReport {
+
+    pub amount_in: String,
+    pub received_amount: String,
+    pub route: Vec<HopReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HopReport {
+
+    pub venue: String,
+    pub from_token: String,
+    pub to_token: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeReport {
+
+    pub venue: String,
+    pub token: String,
+    pub lp_fee: String,
+    pub protocol_fee: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionReport {
+
+    pub name: String,
+    pub received_amount: String,
+    pub gas_units: u64,
+    pub net_amount: String,
+}
+
+//a wallet's balances on one network
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceReport {
+
+    pub wallet_address: String,
+    pub network: String,
+    pub balances: Vec<BalanceRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceRow {
+
+    pub token: String,
+    pub amount: String,
+}
+
+//every exchange's and aggregator's quote for a pair, ranked like `compare_quotes` returns them
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteTable {
+
+    pub network: String,
+    pub from_token: String,
+    pub to_token: String,
+    pub amount_in: String,
+    pub quotes: Vec<QuoteRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteRow {
+
+    pub rank: Option<usize>,             //None when the venue can't do the swap
+    pub venue: String,
+    pub received_amount: Option<String>,
+    pub rate: Option<f64>,               //execution price, to_token per from_token
+    pub net_amount: Option<String>,      //received less gas
+    pub liquidity: Option<String>,
+    pub gas_units: u64,
+    pub error: Option<String>,           //why the venue can't do the swap, e.g. "not enough liquidity"
+    pub code: Option<i32>,               //the error's exit code, see `SwapError::exit_code`
+}
+
+impl SwapReport {
+
+    pub fn from_result(result: &SwapResult, network: Network, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Self {
+
+        SwapReport {
+            network: format!("{:?}", network),
+            from_token: from.to_string(),
+            to_token: to.to_string(),
+            amount_in: amount_in.to_string(),
+            received_amount: result.received_amount.to_string(),
+            aggregator: result.aggregator.clone(),
+            spot_price: result.spot_price,
+            execution_price: result.execution_price,
+            price_impact_bps: result.price_impact_bps,
+            slippage: result.slippage,
+            gas_units: result.gas_units,
+            gas_cost: result.gas_cost.to_string(),
+            gas_cost_usd: result.gas_cost_usd,
+            legs: leg_reports(&result.legs),
+            fees: result.fees.iter().map(|fee| fee_report(&fee.venue_name, &fee.token, fee.lp_fee, fee.protocol_fee)).collect(),
+            options: option_reports(&result.options),
+        }
+    }
+
+    pub fn from_plan(plan: &SwapPlan, aggregators: &[Aggregator], gas: &GasModel, network: Network, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Self {
+
+        let received_amount = plan.quote.received_amount;
+        let gas_cost = gas.cost_in(network, plan.gas_units, to, received_amount.decimals)
+            .unwrap_or(TokenAmount::zero(received_amount.decimals));
+
+        // what every hop is quoted to take, then the aggregator's fee on what the legs pay out
+        let mut fees: Vec<FeeReport> = plan.legs.iter()
+            .flat_map(|leg| leg.route.iter())
+            .map(|hop| fee_report(&hop.venue_name, &hop.from_token, hop.quote.lp_fee, hop.quote.protocol_fee))
+            .collect();
+
+        if let Some(aggregator) = plan.aggregator.map(|index| &aggregators[index]) {
+            let legs_received = plan.legs.iter()
+                .try_fold(TokenAmount::zero(received_amount.decimals), |total, leg| total.checked_add(leg.quote.received_amount));
+
+            if let Ok(fee) = legs_received.and_then(|legs_received| aggregator.fee_on(legs_received)) {
+                fees.push(fee_report(aggregator.name(), to, TokenAmount::zero(fee.decimals), fee));
+            }
+        }
+
+        SwapReport {
+            network: format!("{:?}", network),
+            from_token: from.to_string(),
+            to_token: to.to_string(),
+            amount_in: amount_in.to_string(),
+            received_amount: received_amount.to_string(),
+            aggregator: plan.aggregator.map(|index| aggregators[index].name().to_string()),
+            spot_price: plan.quote.spot_price,
+            execution_price: plan.quote.execution_price,
+            price_impact_bps: plan.quote.price_impact_bps(),
+            slippage: plan.quote.slippage(),
+            gas_units: plan.gas_units,
+            gas_cost: gas_cost.to_string(),
+            gas_cost_usd: gas.cost_usd(network, plan.gas_units),
+            legs: leg_reports(&plan.legs),
+            fees,
+            options: option_reports(&plan.options),
+        }
+    }
+}
+
+//One line; the legs go in `route` as "1300 via Uniswap (WETH -> USDC); 700 via ...", the fees
+//and options are only in the JSON.
+impl Report for SwapReport {
+
+    fn csv(&self) -> String {
+
+        let route: Vec<String> = self.legs.iter()
+            .map(|leg| {
+                let hops: Vec<String> = leg.route.iter().map(|hop| format!("{} ({} -> {})", hop.venue, hop.from_token, hop.to_token)).collect();
+                format!("{} via {}", leg.amount_in, hops.join(", "))
+            })
+            .collect();
+
+        csv_lines(
+            &["network", "from_token", "to_token", "amount_in", "received_amount", "aggregator", "spot_price", "execution_price",
+                "price_impact_bps", "slippage", "gas_units", "gas_cost", "gas_cost_usd", "route"],
+            &[vec![
+                self.network.clone(), self.from_token.clone(), self.to_token.clone(), self.amount_in.clone(), self.received_amount.clone(),
+                self.aggregator.clone().unwrap_or_default(), self.spot_price.to_string(), self.execution_price.to_string(),
+                self.price_impact_bps.to_string(), self.slippage.to_string(), self.gas_units.to_string(), self.gas_cost.clone(),
+                self.gas_cost_usd.to_string(), route.join("; "),
+            ]],
+        )
+    }
+}
+
+impl BalanceReport {
+
+    //every token registered on the network the user holds, in registry order
+    pub fn new(user: &User, registry: &TokenRegistry, network: Network) -> Self {
+
+        BalanceReport {
+            wallet_address: user.wallet_address.clone(),
+            network: format!("{:?}", network),
+            balances: registry.tokens_on(network).iter()
+                .filter_map(|info| user.balance(network, &info.symbol).map(|amount| BalanceRow { token: info.symbol.to_string(), amount: amount.to_string() }))
+                .collect(),
+        }
+    }
+}
+
+//one line per token
+impl Report for BalanceReport {
+
+    fn csv(&self) -> String {
+
+        let rows: Vec<Vec<String>> = self.balances.iter()
+            .map(|row| vec![self.wallet_address.clone(), self.network.clone(), row.token.clone(), row.amount.clone()])
+            .collect();
+
+        csv_lines(&["wallet_address", "network", "token", "amount"], &rows)
+    }
+}
+
+impl QuoteTable {
+
+    pub fn new(quotes: &[VenueQuote], network: Network, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Self {
+
+        QuoteTable {
+            network: format!("{:?}", network),
+            from_token: from.to_string(),
+            to_token: to.to_string(),
+            amount_in: amount_in.to_string(),
+            quotes: quotes.iter().enumerate()
+                .map(|(index, venue_quote)| {
+                    let quote = venue_quote.quote.as_ref().ok();
+
+                    QuoteRow {
+                        rank: quote.map(|_| index + 1),
+                        venue: venue_quote.venue_name.clone(),
+                        received_amount: quote.map(|quote| quote.received_amount.to_string()),
+                        rate: quote.map(|quote| quote.execution_price),
+                        net_amount: venue_quote.net_amount.map(|net_amount| net_amount.to_string()),
+                        liquidity: venue_quote.liquidity.map(|liquidity| liquidity.to_string()),
+                        gas_units: venue_quote.gas_units,
+                        error: venue_quote.quote.as_ref().err().map(|error| error.to_string()),
+                        code: venue_quote.quote.as_ref().err().map(|error| error.exit_code()),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+//one line per venue, empty fields where it has nothing to say
+impl Report for QuoteTable {
+
+    fn csv(&self) -> String {
+
+        let rows: Vec<Vec<String>> = self.quotes.iter()
+            .map(|row| vec![
+                row.rank.map(|rank| rank.to_string()).unwrap_or_default(),
+                row.venue.clone(),
+                row.received_amount.clone().unwrap_or_default(),
+                row.rate.map(|rate| rate.to_string()).unwrap_or_default(),
+                row.net_amount.clone().unwrap_or_default(),
+                row.liquidity.clone().unwrap_or_default(),
+                row.gas_units.to_string(),
+                row.error.clone().unwrap_or_default(),
+                row.code.map(|code| code.to_string()).unwrap_or_default(),
+            ])
+            .collect();
+
+        csv_lines(&["rank", "venue", "received_amount", "rate", "net_amount", "liquidity", "gas_units", "error", "code"], &rows)
+    }
+}
+
+fn leg_reports(legs: &[SwapLeg]) -> Vec<LegReport> {
+
+    legs.iter()
+        .map(|leg| LegReport {
+            amount_in: leg.amount_in.to_string(),
+            received_amount: leg.quote.received_amount.to_string(),
+            route: leg.route.iter()
+                .map(|hop| HopReport { venue: hop.venue_name.clone(), from_token: hop.from_token.to_string(), to_token: hop.to_token.to_string() })
+                .collect(),
+        })
+        .collect()
+}
+
+fn fee_report(venue: &str, token: &TokenType, lp_fee: TokenAmount, protocol_fee: TokenAmount) -> FeeReport {
+
+    FeeReport { venue: venue.to_string(), token: token.to_string(), lp_fee: lp_fee.to_string(), protocol_fee: protocol_fee.to_string() }
+}
+
+fn option_reports(options: &[SwapOption]) -> Vec<OptionReport> {
+
+    options.iter()
+        .map(|option| OptionReport {
+            name: option.name.clone(),
+            received_amount: option.received_amount.to_string(),
+            gas_units: option.gas_units,
+            net_amount: option.net_amount.to_string(),
+        })
+        .collect()
+}
+
+//A header and the rows, RFC 4180 quoting; a field with a comma, quote or line break is put
+//in quotes and its quotes are doubled.
+fn csv_lines(header: &[&str], rows: &[Vec<String>]) -> String {
+
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+
+    let mut csv = header.join(",") + "\n";
+
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|value| field(value)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::error::SwapError;
+    use crate::exchange::{Exchange, ExchangeData};
+    use crate::pricing::SwapQuote;
+    use crate::swap::plan_swap;
+
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    fn plan_report(aggregators: &[Aggregator], aggregator: Option<usize>) -> (SwapPlan, SwapReport) {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+        let amount = TokenAmount::whole(1000, 6).unwrap();
+
+        let mut plan = plan_swap(&venues, &[], &config.gas, Network::Ethereum, &usdc, &usdt, amount).unwrap();
+        plan.aggregator = aggregator;
+        let report = SwapReport::from_plan(&plan, aggregators, &config.gas, Network::Ethereum, &usdc, &usdt, amount);
+
+        (plan, report)
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_line_breaks() {
+
+        let csv = csv_lines(&["a", "b"], &[
+            vec!["plain".to_string(), "1,300 via Uniswap".to_string()],
+            vec!["say \"hi\"".to_string(), "two\nlines".to_string()],
+        ]);
+
+        assert_eq!(csv, "a,b\nplain,\"1,300 via Uniswap\"\n\"say \"\"hi\"\"\",\"two\nlines\"\n");
+    }
+
+    #[test]
+    fn plan_report_json_has_every_field() {
+
+        let (plan, report) = plan_report(&[], None);
+        let json: serde_json::Value = serde_json::from_str(&report.json()).unwrap();
+
+        let mut fields: Vec<&str> = json.as_object().unwrap().keys().map(|key| key.as_str()).collect();
+        fields.sort();
+        assert_eq!(fields, ["aggregator", "amount_in", "execution_price", "fees", "from_token", "gas_cost", "gas_cost_usd", "gas_units",
+            "legs", "network", "options", "price_impact_bps", "received_amount", "slippage", "spot_price", "to_token"]);
+
+        // amounts are strings so they keep every base unit, prices are numbers
+        assert_eq!(json["amount_in"], "1000");
+        assert_eq!(json["received_amount"], plan.quote.received_amount.to_string());
+        assert!(json["execution_price"].is_number());
+        assert!(json["aggregator"].is_null());
+        assert_eq!(json["legs"][0]["route"][0]["venue"], "Uniswap");
+
+        // a quote carries the fees the pool is quoted to take, in the token going in
+        let hop = &plan.legs[0].route[0];
+        assert_eq!(json["fees"].as_array().unwrap().len(), 1);
+        assert_eq!(json["fees"][0]["venue"], "Uniswap");
+        assert_eq!(json["fees"][0]["token"], "USDC");
+        assert_eq!(json["fees"][0]["lp_fee"], hop.quote.lp_fee.to_string());
+        assert_eq!(json["fees"][0]["protocol_fee"], hop.quote.protocol_fee.to_string());
+    }
+
+    #[test]
+    fn plan_report_includes_the_aggregator_fee() {
+
+        let aggregators = [Aggregator::new(Exchange::Matcha, Network::Ethereum, 30, 40_000)];
+        let (plan, report) = plan_report(&aggregators, Some(0));
+
+        let fee = aggregators[0].fee_on(plan.legs[0].quote.received_amount).unwrap();
+        let aggregator_fee = report.fees.last().unwrap();
+
+        assert_eq!(report.fees.len(), 2);
+        assert_eq!(report.aggregator.as_deref(), Some("Matcha"));
+        assert_eq!(aggregator_fee.venue, "Matcha");
+        assert_eq!(aggregator_fee.token, "USDT");
+        assert_eq!(aggregator_fee.lp_fee, "0");
+        assert_eq!(aggregator_fee.protocol_fee, fee.to_string());
+    }
+
+    #[test]
+    fn failed_venues_have_no_rank_and_say_why() {
+
+        let quote = SwapQuote {
+            received_amount: TokenAmount::whole(99, 6).unwrap(),
+            spot_price: 1.0,
+            execution_price: 0.99,
+            price_after: 0.98,
+            lp_fee: TokenAmount::zero(6),
+            protocol_fee: TokenAmount::zero(6),
+        };
+        let quotes = [
+            VenueQuote {
+                venue_name: "Uniswap".to_string(),
+                quote: Ok(quote),
+                liquidity: Some(TokenAmount::whole(1000, 6).unwrap()),
+                gas_units: 150_000,
+                net_amount: Some(TokenAmount::whole(99, 6).unwrap()),
+            },
+            VenueQuote {
+                venue_name: "Sushi".to_string(),
+                quote: Err(SwapError::NotEnoughLiquidity),
+                liquidity: Some(TokenAmount::zero(6)),
+                gas_units: 150_000,
+                net_amount: None,
+            },
+        ];
+
+        let table = QuoteTable::new(&quotes, Network::Ethereum, &TokenType::new("USDC"), &TokenType::new("USDT"), TokenAmount::whole(100, 6).unwrap());
+
+        assert_eq!(table.quotes[0].rank, Some(1));
+        assert_eq!(table.quotes[0].error, None);
+        assert_eq!(table.quotes[0].code, None);
+        assert_eq!(table.quotes[1].rank, None);
+        assert_eq!(table.quotes[1].received_amount, None);
+        assert_eq!(table.quotes[1].error, Some(SwapError::NotEnoughLiquidity.to_string()));
+        assert_eq!(table.quotes[1].code, Some(SwapError::NotEnoughLiquidity.exit_code()));
+
+        let expected = format!(",Sushi,,,,0,150000,{},{}", SwapError::NotEnoughLiquidity, SwapError::NotEnoughLiquidity.exit_code());
+        assert_eq!(table.csv().lines().nth(2), Some(expected.as_str()));
+    }
+}
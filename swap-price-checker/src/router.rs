@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::gas::GasModel;
+use crate::pricing::SwapQuote;
+use crate::token::{TokenAmount, TokenType};
+use crate::venue::Venue;
+
+//one step of a route, a single pool on a single exchange
+#[derive(Debug, Clone)]
+pub struct RouteHop {
+
+    pub venue_index: usize,      //position of the venue in the venues slice
+    pub venue_name: String,
+    pub from_token: TokenType,
+    pub to_token: TokenType,
+    pub amount_in: TokenAmount,
+    pub quote: SwapQuote,
+    pub gas_units: u64,          //gas the venue uses for this hop
+}
+
+//part of an order sent down one route, a split order has several legs
+#[derive(Debug, Clone)]
+pub struct SwapLeg {
+
+    pub route: Vec<RouteHop>,    //pools the leg goes through, one hop for a direct swap
+    pub amount_in: TokenAmount,  //portion of the swap amount sent down this route
+    pub quote: SwapQuote,        //combined quote of every hop on the route
+    pub gas_units: u64,          //gas of every hop on the route
+}
+
+//maximum number of pools a route may go through, USDC -> BUSD -> USDT is 2 hops
+pub const MAX_HOPS: usize = 3;
+
+//Searches every token path from one token to another, within one venue and across venues
+pub struct Router<'a> {
+
+    venues: &'a [Box<dyn Venue>],
+    gas: &'a GasModel,
+    network: Network,    //only venues on this network are used
+    neighbours: HashMap<TokenType, Vec<TokenType>>,   //tokens reachable in one hop on any venue
+    to: TokenType,
+    min_hops: usize,     //1 unless the route has to go through another token
+    best_route: Option<Vec<RouteHop>>,
+    error: SwapError,    //reported when no route is found
+}
+
+impl<'a> Router<'a> {
+
+    //find the route with the best output after gas on a network, direct or through intermediate tokens
+    pub fn find_best_route(venues: &'a [Box<dyn Venue>], gas: &'a GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<Vec<RouteHop>, SwapError> {
+
+        Self::find(venues, gas, network, from, to, amount, 1)
+    }
+
+    //the best route that goes through at least one intermediate token, never a direct pool
+    pub fn find_best_multi_hop_route(venues: &'a [Box<dyn Venue>], gas: &'a GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<Vec<RouteHop>, SwapError> {
+
+        Self::find(venues, gas, network, from, to, amount, 2)
+    }
+
+    fn find(venues: &'a [Box<dyn Venue>], gas: &'a GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount, min_hops: usize) -> Result<Vec<RouteHop>, SwapError> {
+
+        if !venues.iter().any(|venue| venue.network() == network) {
+            return Err(SwapError::IncorrectNetwork);
+        }
+
+        let mut neighbours: HashMap<TokenType, Vec<TokenType>> = HashMap::new();
+
+        for (pair_from, pair_to) in venues.iter().filter(|venue| venue.network() == network).flat_map(|venue| venue.supported_pairs()) {
+            let reachable = neighbours.entry(pair_from).or_default();
+            if !reachable.contains(&pair_to) {
+                reachable.push(pair_to);
+            }
+        }
+
+        let mut router = Router {
+            venues,
+            gas,
+            network,
+            neighbours,
+            to: to.clone(),
+            min_hops,
+            best_route: None,
+            error: SwapError::TokenPairNotSupported,
+        };
+
+        router.search(&mut vec![from.clone()], &mut Vec::new(), amount);
+        router.best_route.ok_or(router.error)
+    }
+
+    //depth first search over token paths. Each hop only goes through different pools,
+    //so picking the best venue per hop also gives the best output for the whole path.
+    //Every hop pays its own gas, so a longer path has to pay out more to win.
+    fn search(&mut self, path: &mut Vec<TokenType>, hops: &mut Vec<RouteHop>, amount_in: TokenAmount) {
+
+        let current = path.last().unwrap().clone();
+        let reachable = self.neighbours.get(&current).cloned().unwrap_or_default();
+
+        for next in reachable {
+
+            // never visit a token twice
+            if path.contains(&next) {
+                continue;
+            }
+
+            let hop = match self.best_hop(&current, &next, amount_in) {
+                Ok(hop) => hop,
+                Err(error) => {
+                    // a pool that exists but is too shallow is a better explanation than no pool at all
+                    if let SwapError::NotEnoughLiquidity = error {
+                        self.error = error;
+                    }
+                    continue;
+                }
+            };
+
+            if next == self.to {
+
+                if hops.len() + 1 < self.min_hops {
+                    continue;
+                }
+
+                let gas_units = hops.iter().map(|hop| hop.gas_units).sum::<u64>() + hop.gas_units;
+                let net = net_units(self.gas, self.network, gas_units, &self.to, hop.quote.received_amount);
+
+                let is_better = match &self.best_route {
+                    Some(route) => {
+                        let best_gas_units = route.iter().map(|hop| hop.gas_units).sum();
+                        net > net_units(self.gas, self.network, best_gas_units, &self.to, route.last().unwrap().quote.received_amount)
+                    },
+                    None => true,
+                };
+
+                if is_better {
+                    let mut route = hops.clone();
+                    route.push(hop);
+                    self.best_route = Some(route);
+                }
+            } else if path.len() < MAX_HOPS {
+
+                let received_amount = hop.quote.received_amount;
+
+                path.push(next);
+                hops.push(hop);
+                self.search(path, hops, received_amount);
+                hops.pop();
+                path.pop();
+            }
+        }
+    }
+
+    //best single venue for one hop, after the gas it costs
+    fn best_hop(&self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<RouteHop, SwapError> {
+
+        let mut best_hop: Option<RouteHop> = None;
+        let mut error = SwapError::TokenPairNotSupported;
+
+        for (index, venue) in self.venues.iter().enumerate() {
+
+            if venue.network() != self.network {
+                continue;
+            }
+
+            match venue.quote(from, to, amount_in) {
+                Ok(quote) => {
+                    let net = net_units(self.gas, self.network, venue.gas_units(), to, quote.received_amount);
+                    let is_better = match &best_hop {
+                        Some(hop) => net > net_units(self.gas, self.network, hop.gas_units, to, hop.quote.received_amount),
+                        None => true,
+                    };
+
+                    if is_better {
+                        best_hop = Some(RouteHop {
+                            venue_index: index,
+                            venue_name: venue.name().to_string(),
+                            from_token: from.clone(),
+                            to_token: to.clone(),
+                            amount_in,
+                            quote,
+                            gas_units: venue.gas_units(),
+                        });
+                    }
+                },
+                Err(SwapError::NotEnoughLiquidity) => error = SwapError::NotEnoughLiquidity,
+                Err(_) => continue,
+            }
+        }
+
+        best_hop.ok_or(error)
+    }
+}
+
+//number of chunks an order is cut into when looking for the best split
+pub const SPLIT_CHUNKS: usize = 20;
+
+//the venue and pair of every hop down one way a split can send its chunks
+type SplitPath = Vec<(usize, TokenType, TokenType)>;
+
+//Split an order across the venues on a network according to their depth. Each chunk goes to
+//the venue whose pool adds the most output on top of what it has already been given,
+//a venue that hasn't been used yet also has to pay for its gas out of that.
+pub fn find_best_split(venues: &[Box<dyn Venue>], gas: &GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<Vec<SwapLeg>, SwapError> {
+
+    if !venues.iter().any(|venue| venue.network() == network) {
+        return Err(SwapError::IncorrectNetwork);
+    }
+
+    split_over_paths(venues, gas, network, to, amount, direct_paths(venues, network, from, to))
+}
+
+//Like find_best_split, but the best route through other tokens takes chunks too, next to
+//the direct pools. None of its pools swap from -> to, so it never competes with them for
+//the same liquidity.
+pub fn find_best_split_with_route(venues: &[Box<dyn Venue>], gas: &GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<Vec<SwapLeg>, SwapError> {
+
+    if !venues.iter().any(|venue| venue.network() == network) {
+        return Err(SwapError::IncorrectNetwork);
+    }
+
+    let mut paths = direct_paths(venues, network, from, to);
+
+    if let Ok(route) = Router::find_best_multi_hop_route(venues, gas, network, from, to, amount) {
+        paths.push(route.iter().map(|hop| (hop.venue_index, hop.from_token.clone(), hop.to_token.clone())).collect());
+    }
+
+    split_over_paths(venues, gas, network, to, amount, paths)
+}
+
+//the direct pool of every venue on the network, whether or not it has the pair
+fn direct_paths(venues: &[Box<dyn Venue>], network: Network, from: &TokenType, to: &TokenType) -> Vec<SplitPath> {
+
+    venues.iter().enumerate()
+        .filter(|(_, venue)| venue.network() == network)
+        .map(|(index, _)| vec![(index, from.clone(), to.clone())])
+        .collect()
+}
+
+//quote every hop of a path, each passing on what the previous one pays out
+fn quote_path(venues: &[Box<dyn Venue>], path: &SplitPath, amount: TokenAmount) -> Result<Vec<RouteHop>, SwapError> {
+
+    let mut route = Vec::new();
+    let mut amount_in = amount;
+
+    for (index, from, to) in path {
+
+        let venue = &venues[*index];
+        let quote = venue.quote(from, to, amount_in)?;
+
+        route.push(RouteHop {
+            venue_index: *index,
+            venue_name: venue.name().to_string(),
+            from_token: from.clone(),
+            to_token: to.clone(),
+            amount_in,
+            quote,
+            gas_units: venue.gas_units(),
+        });
+
+        amount_in = quote.received_amount;
+    }
+
+    Ok(route)
+}
+
+//hand the order out in SPLIT_CHUNKS chunks, each to the path that adds the most output
+fn split_over_paths(venues: &[Box<dyn Venue>], gas: &GasModel, network: Network, to: &TokenType, amount: TokenAmount, paths: Vec<SplitPath>) -> Result<Vec<SwapLeg>, SwapError> {
+
+    let chunk = amount.units / SPLIT_CHUNKS as u128;
+    let mut allocations = vec![TokenAmount::zero(amount.decimals); paths.len()];
+    let mut outputs: Vec<u128> = vec![0; paths.len()];   //base units each path pays out for its allocation
+
+    for round in 0..SPLIT_CHUNKS {
+
+        // the last chunk picks up whatever the division left over
+        let chunk = if round == SPLIT_CHUNKS - 1 {
+            TokenAmount::from_units(amount.units - chunk * (SPLIT_CHUNKS as u128 - 1), amount.decimals)
+        } else {
+            TokenAmount::from_units(chunk, amount.decimals)
+        };
+
+        if chunk.is_zero() {
+            continue;
+        }
+
+        let mut best: Option<(usize, u128, i128)> = None;
+        let mut error = SwapError::TokenPairNotSupported;
+
+        for (index, path) in paths.iter().enumerate() {
+
+            match quote_path(venues, path, allocations[index].checked_add(chunk)?) {
+                Ok(route) => {
+                    let received = route.last().unwrap().quote.received_amount;
+                    let mut gain = received.units as i128 - outputs[index] as i128;
+
+                    if allocations[index].is_zero() {
+                        let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+                        gain -= gas_units_cost(gas, network, gas_units, to, received.decimals) as i128;
+                    }
+
+                    let is_better = match &best {
+                        Some((_, _, best_gain)) => gain > *best_gain,
+                        None => true,
+                    };
+
+                    if is_better {
+                        best = Some((index, received.units, gain));
+                    }
+                },
+                Err(SwapError::NotEnoughLiquidity) => error = SwapError::NotEnoughLiquidity,
+                Err(_) => continue,
+            }
+        }
+
+        let (index, received, _) = best.ok_or(error)?;
+        allocations[index] = allocations[index].checked_add(chunk)?;
+        outputs[index] = received;
+    }
+
+    let mut legs = Vec::new();
+
+    for (index, path) in paths.iter().enumerate() {
+
+        if !allocations[index].is_zero() {
+
+            let route = quote_path(venues, path, allocations[index])?;
+
+            // a direct leg keeps its pool's own quote, fees and all
+            let quote = if route.len() == 1 { route[0].quote } else { route_quote(&route)? };
+            let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+
+            legs.push(SwapLeg {
+                route,
+                amount_in: allocations[index],
+                quote,
+                gas_units,
+            });
+        }
+    }
+
+    Ok(legs)
+}
+
+//combine the quotes of every leg into a single quote for the whole order
+//An empty order is InvalidAmount.
+pub fn order_quote(legs: &[SwapLeg]) -> Result<SwapQuote, SwapError> {
+
+    let first = legs.first().ok_or(SwapError::InvalidAmount)?;
+    let mut amount_in = TokenAmount::zero(first.amount_in.decimals);
+    let mut received_amount = TokenAmount::zero(first.quote.received_amount.decimals);
+
+    for leg in legs {
+        amount_in = amount_in.checked_add(leg.amount_in)?;
+        received_amount = received_amount.checked_add(leg.quote.received_amount)?;
+    }
+
+    // the best price on offer before trading is what the order is quoted against
+    let spot_price = legs.iter().map(|leg| leg.quote.spot_price).fold(0.0, f64::max);
+
+    // each pool moves by its own amount, weighted by how much of the order it took
+    let price_move: f64 = legs.iter()
+        .map(|leg| leg.amount_in.to_f64() / amount_in.to_f64() * leg.quote.price_after / leg.quote.spot_price)
+        .sum();
+
+    Ok(SwapQuote {
+        received_amount,
+        spot_price,
+        execution_price: received_amount.to_f64() / amount_in.to_f64(),
+        price_after: spot_price * price_move,
+        lp_fee: TokenAmount::zero(amount_in.decimals),
+        protocol_fee: TokenAmount::zero(amount_in.decimals),
+    })
+}
+
+//combine the quotes of every hop into a single quote for the whole route, an empty route is InvalidAmount
+pub fn route_quote(route: &[RouteHop]) -> Result<SwapQuote, SwapError> {
+
+    let (Some(first), Some(last)) = (route.first(), route.last()) else {
+        return Err(SwapError::InvalidAmount);
+    };
+    let amount_in = first.amount_in;
+    let received_amount = last.quote.received_amount;
+
+    Ok(SwapQuote {
+        received_amount,
+        spot_price: route.iter().map(|hop| hop.quote.spot_price).product(),
+        execution_price: received_amount.to_f64() / amount_in.to_f64(),
+        price_after: route.iter().map(|hop| hop.quote.price_after).product(),
+        lp_fee: TokenAmount::zero(amount_in.decimals),
+        protocol_fee: TokenAmount::zero(amount_in.decimals),
+    })
+}
+
+//gas of every leg of an order
+pub fn order_gas_units(legs: &[SwapLeg]) -> u64 {
+
+    legs.iter().map(|leg| leg.gas_units).sum()
+}
+
+//output left in base units once gas is paid. A token the gas model can't price is
+//ranked on its output alone.
+pub fn net_units(gas: &GasModel, network: Network, gas_units: u64, token: &TokenType, received_amount: TokenAmount) -> u128 {
+
+    gas.net_output(network, gas_units, token, received_amount)
+        .unwrap_or(received_amount)
+        .units
+}
+
+//cost of gas in base units of a token, zero if the gas model can't price it
+fn gas_units_cost(gas: &GasModel, network: Network, gas_units: u64, token: &TokenType, decimals: u8) -> u128 {
+
+    gas.cost_in(network, gas_units, token, decimals)
+        .map(|cost| cost.units)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+
+    // Pools only go one way. USDC -> USDT on both exchanges, then a chain on to DAI, BUSD and
+    // FRAX one pool at a time, and USDT back to USDC so there's a cycle to avoid.
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "DAI"
+        name = "Dai"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "BUSD"
+        name = "Binance USD"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "FRAX"
+        name = "Frax"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "USDT"
+        to = "DAI"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "DAI"
+        to = "BUSD"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Sushi"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "USDT"
+        to = "USDC"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "BUSD"
+        to = "FRAX"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    fn token(symbol: &str) -> TokenType {
+
+        TokenType::new(symbol)
+    }
+
+    fn route_tokens(route: &[RouteHop]) -> Vec<&str> {
+
+        let mut tokens = vec![route[0].from_token.symbol()];
+        tokens.extend(route.iter().map(|hop| hop.to_token.symbol()));
+        tokens
+    }
+
+    #[test]
+    fn empty_order_and_route_are_invalid_amounts() {
+
+        assert!(matches!(order_quote(&[]), Err(SwapError::InvalidAmount)));
+        assert!(matches!(route_quote(&[]), Err(SwapError::InvalidAmount)));
+    }
+
+    #[test]
+    fn routes_through_other_tokens_without_a_direct_pool() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let amount = TokenAmount::whole(1_000, 6).unwrap();
+
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("DAI"), amount).unwrap();
+        assert_eq!(route_tokens(&route), ["USDC", "USDT", "DAI"]);
+
+        // three hops, the last one on another exchange, each passing on what the previous paid out
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDT"), &token("FRAX"), amount).unwrap();
+        assert_eq!(route_tokens(&route), ["USDT", "DAI", "BUSD", "FRAX"]);
+        assert_eq!(route[2].venue_name, "Sushi");
+        assert_eq!(route[0].amount_in, amount);
+        for pair in route.windows(2) {
+            assert_eq!(pair[1].amount_in, pair[0].quote.received_amount);
+        }
+    }
+
+    #[test]
+    fn routes_stop_at_max_hops() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let amount = TokenAmount::whole(1_000, 6).unwrap();
+
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("BUSD"), amount).unwrap();
+        assert_eq!(route.len(), MAX_HOPS);
+
+        // USDC -> USDT -> DAI -> BUSD -> FRAX is a hop too many
+        let result = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("FRAX"), amount);
+        assert!(matches!(result, Err(SwapError::TokenPairNotSupported)));
+    }
+
+    #[test]
+    fn routes_never_visit_a_token_twice() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let amount = TokenAmount::whole(1_000, 6).unwrap();
+
+        // USDC -> USDT -> USDC exists on Sushi, but it would come back to where it started
+        let result = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("USDC"), amount);
+        assert!(matches!(result, Err(SwapError::TokenPairNotSupported)));
+
+        // USDT -> USDC -> USDT -> DAI would be within MAX_HOPS too
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDT"), &token("DAI"), amount).unwrap();
+        assert_eq!(route_tokens(&route), ["USDT", "DAI"]);
+
+        let route = Router::find_best_multi_hop_route(&venues, &config.gas, Network::Ethereum, &token("USDT"), &token("DAI"), amount);
+        assert!(matches!(route, Err(SwapError::TokenPairNotSupported)));
+    }
+
+    #[test]
+    fn split_beats_one_venue_on_a_large_order() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let amount = TokenAmount::whole(200_000, 6).unwrap();
+
+        let route = Router::find_best_route(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("USDT"), amount).unwrap();
+        let legs = find_best_split(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("USDT"), amount).unwrap();
+
+        // two pools of the same depth, so the order is shared out evenly
+        assert_eq!(legs.len(), 2);
+        assert_eq!(legs[0].amount_in, legs[1].amount_in);
+        assert!(order_quote(&legs).unwrap().received_amount > route.last().unwrap().quote.received_amount);
+    }
+
+    #[test]
+    fn split_legs_add_up_to_the_order() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+
+        // SPLIT_CHUNKS doesn't divide these, 19 units doesn't even make one chunk
+        for amount in ["123456.789013", "0.000019"] {
+            let amount = TokenAmount::parse(amount, 6).unwrap();
+            let legs = find_best_split(&venues, &config.gas, Network::Ethereum, &token("USDC"), &token("USDT"), amount).unwrap();
+
+            let total = legs.iter().try_fold(TokenAmount::zero(6), |total, leg| total.checked_add(leg.amount_in)).unwrap();
+            assert_eq!(total, amount);
+            assert!(legs.iter().all(|leg| !leg.amount_in.is_zero()));
+        }
+    }
+}
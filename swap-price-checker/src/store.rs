@@ -0,0 +1,538 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch::BatchAuction;
+use crate::bridge::PendingTransfer;
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::liquidity::remove_liquidity;
+use crate::strategy::withdraw_strategy;
+use crate::token::{TokenAmount, TokenRegistry, TokenType};
+use crate::user::User;
+use crate::venue::Venue;
+
+// Users kept in a JSON file between runs, see `UserDatabase::open`. What belongs to the wallet
+// is kept; its network, balances and bridge transfers on their way, with the bridge's block
+// clock so they still arrive when they should. Pools, strategies and batch orders are rebuilt
+// from the config on every start, so the users are written as if they had taken everything
+// out of them (`close_out`); their LP positions, strategies and orders come back as balances.
+
+//Everything that can go wrong while reading or writing the users file
+#[derive(Debug)]
+pub enum StoreError {
+
+    Io(String),
+    Parse(String),
+    Market(SwapError),   //the users' positions couldn't be closed out, nothing was written
+}
+
+impl fmt::Display for StoreError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            StoreError::Io(error) => write!(f, "could not access users file: {}", error),
+            StoreError::Parse(error) => write!(f, "could not parse users file: {}", error),
+            StoreError::Market(error) => write!(f, "could not close out the users' positions: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+
+// The file layout. Amounts are decimal strings with their decimals, so nothing is lost
+// going through f64 and the file stays readable.
+
+#[derive(Serialize, Deserialize)]
+struct UsersFile {
+
+    #[serde(default)]
+    block: u64,   //the bridge's block clock when the users were saved
+    users: Vec<StoredUser>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredUser {
+
+    wallet_address: String,
+    network: String,
+    balances: Vec<StoredBalance>,
+    #[serde(default)]
+    pending_transfers: Vec<StoredTransfer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredBalance {
+
+    network: String,
+    token: String,
+    #[serde(flatten)]
+    amount: StoredAmount,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredTransfer {
+
+    token: String,
+    from_network: String,
+    to_network: String,
+    amount_sent: StoredAmount,
+    fee: StoredAmount,
+    received_amount: StoredAmount,
+    arrival_block: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredAmount {
+
+    amount: String,
+    decimals: u8,
+}
+
+impl From<TokenAmount> for StoredAmount {
+
+    fn from(amount: TokenAmount) -> Self {
+
+        StoredAmount { amount: amount.to_string(), decimals: amount.decimals }
+    }
+}
+
+impl StoredAmount {
+
+    //The amount of a token on a network. The decimals have to be the ones the token is
+    //registered with, or its arithmetic would mix units with every pool and balance.
+    fn amount(&self, tokens: &TokenRegistry, token: &TokenType, network: Network) -> Result<TokenAmount, StoreError> {
+
+        let decimals = tokens.decimals(token, network)
+            .map_err(|_| StoreError::Parse(format!("unknown token `{}` on {:?}", token, network)))?;
+
+        if self.decimals != decimals {
+            return Err(StoreError::Parse(format!("`{}` on {:?} has {} decimals, not {}", token, network, decimals, self.decimals)));
+        }
+
+        TokenAmount::parse(&self.amount, decimals).map_err(|_| StoreError::Parse(format!("invalid amount `{}`", self.amount)))
+    }
+}
+
+fn network(name: &str) -> Result<Network, StoreError> {
+
+    Network::from_name(name).ok_or_else(|| StoreError::Parse(format!("unknown network `{}`", name)))
+}
+
+//the users in a file and the block they were saved at, none at block 0 if it doesn't exist yet.
+//Every amount is checked against the tokens' registered decimals.
+pub fn read_users(path: &Path, tokens: &TokenRegistry) -> Result<(Vec<User>, u64), StoreError> {
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(error) => return Err(StoreError::Io(error.to_string())),
+    };
+
+    let file: UsersFile = serde_json::from_str(&contents).map_err(|error| StoreError::Parse(error.to_string()))?;
+
+    let users = file.users.into_iter()
+        .map(|stored| {
+            let mut user = User {
+                network: network(&stored.network)?,
+                wallet_address: stored.wallet_address,
+                balances: Default::default(),
+                pending_transfers: Vec::new(),
+                lp_positions: Vec::new(),
+            };
+
+            for balance in stored.balances {
+                let balance_network = network(&balance.network)?;
+                let token = TokenType::new(&balance.token);
+                let amount = balance.amount.amount(tokens, &token, balance_network)?;
+
+                user.balances.entry(balance_network).or_default().insert(token, amount);
+            }
+
+            // the fee is taken before the transfer is rescaled to the destination's decimals
+            for transfer in stored.pending_transfers {
+                let token = TokenType::new(&transfer.token);
+                let from_network = network(&transfer.from_network)?;
+                let to_network = network(&transfer.to_network)?;
+
+                user.pending_transfers.push(PendingTransfer {
+                    amount_sent: transfer.amount_sent.amount(tokens, &token, from_network)?,
+                    fee: transfer.fee.amount(tokens, &token, from_network)?,
+                    received_amount: transfer.received_amount.amount(tokens, &token, to_network)?,
+                    token,
+                    from_network,
+                    to_network,
+                    arrival_block: transfer.arrival_block,
+                });
+            }
+
+            Ok(user)
+        })
+        .collect::<Result<_, StoreError>>()?;
+
+    Ok((users, file.block))
+}
+
+//Replace the file with the users, crash-safe. They're written to a temporary file next to
+//it which is synced to disk and then renamed over the old one, so the file is always either
+//the old or the new version, never half written.
+pub fn write_users(path: &Path, users: &[User], block: u64) -> Result<(), StoreError> {
+
+    let file = UsersFile {
+        block,
+        users: users.iter()
+            .map(|user| {
+                // balances sorted so saving the same users gives the same file
+                let mut balances: Vec<StoredBalance> = user.balances.iter()
+                    .flat_map(|(network, balances)| balances.iter().map(move |(token, amount)| StoredBalance {
+                        network: format!("{:?}", network),
+                        token: token.to_string(),
+                        amount: StoredAmount::from(*amount),
+                    }))
+                    .collect();
+                balances.sort_by(|a, b| (&a.network, &a.token).cmp(&(&b.network, &b.token)));
+
+                StoredUser {
+                    wallet_address: user.wallet_address.clone(),
+                    network: format!("{:?}", user.network),
+                    balances,
+                    pending_transfers: user.pending_transfers.iter()
+                        .map(|transfer| StoredTransfer {
+                            token: transfer.token.to_string(),
+                            from_network: format!("{:?}", transfer.from_network),
+                            to_network: format!("{:?}", transfer.to_network),
+                            amount_sent: transfer.amount_sent.into(),
+                            fee: transfer.fee.into(),
+                            received_amount: transfer.received_amount.into(),
+                            arrival_block: transfer.arrival_block,
+                        })
+                        .collect(),
+                }
+            })
+            .collect(),
+    };
+
+    let contents = serde_json::to_string_pretty(&file).map_err(|error| StoreError::Parse(error.to_string()))?;
+
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = Path::new(&temp_path);
+
+    let io_error = |error: std::io::Error| StoreError::Io(error.to_string());
+
+    let mut temp_file = File::create(temp_path).map_err(io_error)?;
+    temp_file.write_all(contents.as_bytes()).map_err(io_error)?;
+    temp_file.sync_all().map_err(io_error)?;
+    drop(temp_file);
+
+    fs::rename(temp_path, path).map_err(io_error)?;
+
+    // the rename is only durable once the directory holding the file is synced too
+    #[cfg(unix)]
+    {
+        let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(directory).and_then(|directory| directory.sync_all()).map_err(io_error)?;
+    }
+
+    Ok(())
+}
+
+//The users as they'd be after taking everything they have in the market out of it. Their LP
+//positions are removed and their strategies withdrawn on copies of the venues, and their
+//open batch orders refunded. The venues and users passed in don't change.
+pub fn close_out(users: &[User], venues: &[Box<dyn Venue>], auctions: &[BatchAuction]) -> Result<Vec<User>, SwapError> {
+
+    let mut users = users.to_vec();
+    let mut venues: Vec<Box<dyn Venue>> = venues.iter().map(|venue| venue.clone_venue()).collect();
+
+    for user in &mut users {
+        while let Some(position) = user.lp_positions.first() {
+            let shares = position.shares;
+            remove_liquidity(&mut venues, 0, shares, user)?;
+        }
+    }
+
+    for venue_index in 0..venues.len() {
+        let strategies: Vec<(u64, String)> = venues[venue_index].strategies().iter().map(|strategy| (strategy.id, strategy.owner.clone())).collect();
+
+        for (strategy_id, owner) in strategies {
+            if let Some(user) = users.iter_mut().find(|user| user.wallet_address == owner) {
+                withdraw_strategy(&mut venues, venue_index, strategy_id, user)?;
+            }
+        }
+    }
+
+    for auction in auctions {
+        for order in auction.orders() {
+            if let Some(user) = users.iter_mut().find(|user| user.wallet_address == order.wallet_address) {
+                user.add_balance(auction.network, &order.from_token, order.amount_in)?;
+            }
+        }
+    }
+
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+    use crate::liquidity::add_liquidity;
+    use crate::strategy::{StrategyParams, place_strategy};
+    use crate::user::UserDatabase;
+
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "DAI"
+        name = "Dai"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Polygon"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Carbon"
+        network = "Ethereum"
+        maker_strategies = true
+
+        [[exchanges.pairs]]
+        from = "USDT"
+        to = "DAI"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        pricing = { model = "constant_product" }
+
+        [[exchanges.pairs]]
+        from = "DAI"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        pricing = { model = "constant_product" }
+
+        [[batch_auctions]]
+        name = "CowSwap"
+        network = "Ethereum"
+        window_blocks = 5
+    "#;
+
+    //a file in the temp directory that's gone once the test is done
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+
+        fn new(name: &str) -> Self {
+
+            TempFile(std::env::temp_dir().join(format!("swap-price-checker-{}-{}.json", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+
+        fn drop(&mut self) {
+
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn token(symbol: &str) -> TokenType {
+
+        TokenType::new(symbol)
+    }
+
+    #[test]
+    fn users_and_block_round_trip() {
+
+        let file = TempFile::new("round-trip");
+        let config = parse_toml(CONFIG).unwrap();
+
+        let mut user = User::new(Network::Polygon, "0xsaved".to_string(), &config.tokens);
+        user.balances.get_mut(&Network::Ethereum).unwrap().insert(token("DAI"), TokenAmount::parse("1234.000000000000000001", 18).unwrap());
+        user.pending_transfers.push(PendingTransfer {
+            token: token("USDC"),
+            from_network: Network::Ethereum,
+            to_network: Network::Polygon,
+            amount_sent: TokenAmount::whole(100, 6).unwrap(),
+            fee: TokenAmount::parse("0.05", 6).unwrap(),
+            received_amount: TokenAmount::parse("99.95", 6).unwrap(),
+            arrival_block: 57,
+        });
+
+        write_users(&file.0, std::slice::from_ref(&user), 42).unwrap();
+        let (users, block) = read_users(&file.0, &config.tokens).unwrap();
+
+        assert_eq!(block, 42);
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].wallet_address, "0xsaved");
+        assert_eq!(users[0].network, Network::Polygon);
+        assert_eq!(users[0].balances, user.balances);
+
+        let transfer = &users[0].pending_transfers[0];
+        assert_eq!((transfer.from_network, transfer.to_network, transfer.arrival_block), (Network::Ethereum, Network::Polygon, 57));
+        assert_eq!(transfer.received_amount, TokenAmount::parse("99.95", 6).unwrap());
+
+        // nothing left behind but the file itself
+        let mut temp_path = file.0.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        assert!(!Path::new(&temp_path).exists());
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_db() {
+
+        let file = TempFile::new("missing");
+        let config = parse_toml(CONFIG).unwrap();
+        let (users, block) = read_users(&file.0, &config.tokens).unwrap();
+
+        assert!(users.is_empty());
+        assert_eq!(block, 0);
+    }
+
+    #[test]
+    fn bad_file_is_a_parse_error() {
+
+        let file = TempFile::new("bad");
+        let config = parse_toml(CONFIG).unwrap();
+
+        fs::write(&file.0, r#"{"users": [{"wallet_address": "0x1", "network": "Mars", "balances": []}]}"#).unwrap();
+        assert!(matches!(read_users(&file.0, &config.tokens), Err(StoreError::Parse(_))));
+
+        fs::write(&file.0, "{").unwrap();
+        assert!(matches!(read_users(&file.0, &config.tokens), Err(StoreError::Parse(_))));
+    }
+
+    #[test]
+    fn amounts_need_the_registered_decimals() {
+
+        let file = TempFile::new("decimals");
+        let config = parse_toml(CONFIG).unwrap();
+        let users = |balance: &str, transfer: &str| format!(
+            r#"{{"users": [{{"wallet_address": "0x1", "network": "Ethereum", "balances": [{}], "pending_transfers": [{}]}}]}}"#, balance, transfer);
+        let transfer = |received_decimals: u8| format!(
+            r#"{{"token": "USDC", "from_network": "Ethereum", "to_network": "Polygon", "amount_sent": {{"amount": "100", "decimals": 6}},
+                "fee": {{"amount": "0.1", "decimals": 6}}, "received_amount": {{"amount": "99.9", "decimals": {}}}, "arrival_block": 3}}"#, received_decimals);
+
+        fs::write(&file.0, users(r#"{"network": "Ethereum", "token": "DAI", "amount": "1", "decimals": 18}"#, &transfer(6))).unwrap();
+        assert!(read_users(&file.0, &config.tokens).is_ok());
+
+        // DAI is registered with 18, and 200 would overflow rather than just mismatch
+        for decimals in [6, 200] {
+            let balance = format!(r#"{{"network": "Ethereum", "token": "DAI", "amount": "1", "decimals": {}}}"#, decimals);
+            fs::write(&file.0, users(&balance, "")).unwrap();
+            assert!(matches!(read_users(&file.0, &config.tokens), Err(StoreError::Parse(_))));
+        }
+
+        // DAI isn't registered on Polygon at all
+        fs::write(&file.0, users(r#"{"network": "Polygon", "token": "DAI", "amount": "1", "decimals": 18}"#, "")).unwrap();
+        assert!(matches!(read_users(&file.0, &config.tokens), Err(StoreError::Parse(_))));
+
+        fs::write(&file.0, users("", &transfer(18))).unwrap();
+        assert!(matches!(read_users(&file.0, &config.tokens), Err(StoreError::Parse(_))));
+    }
+
+    #[test]
+    fn close_out_pays_back_what_the_market_holds() {
+
+        let mut config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(std::mem::take(&mut config.exchanges));
+        let mut auction = config.auctions.remove(0);
+
+        let mut user = User::new(Network::Ethereum, "0xmaker".to_string(), &config.tokens);
+        for symbol in ["USDC", "USDT", "DAI"] {
+            user.add_balance(Network::Ethereum, &token(symbol), TokenAmount::whole(10_000, if symbol == "DAI" { 18 } else { 6 }).unwrap()).unwrap();
+        }
+        let before = user.balances.clone();
+
+        add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), TokenAmount::whole(1_000, 6).unwrap(), &mut user).unwrap();
+        place_strategy(&mut venues, 1, &StrategyParams {
+            base: token("DAI"),
+            quote: token("USDT"),
+            sell_amount: TokenAmount::whole(500, 18).unwrap(),
+            sell_low: 1.01,
+            sell_high: 1.02,
+            buy_amount: TokenAmount::whole(500, 6).unwrap(),
+            buy_low: 0.98,
+            buy_high: 0.99,
+        }, &mut user).unwrap();
+        auction.place_order(&mut user, &token("USDC"), &token("DAI"), TokenAmount::whole(250, 6).unwrap(), TokenAmount::zero(18), 0).unwrap();
+
+        let users = [user];
+        let closed = close_out(&users, &venues, std::slice::from_ref(&auction)).unwrap();
+
+        // the strategy and the order come back in full, the LP shares lose a unit to rounding at most
+        assert!(closed[0].lp_positions.is_empty());
+        for (token, amount) in &before[&Network::Ethereum] {
+            let after = closed[0].balance(Network::Ethereum, token).unwrap();
+            assert!(after <= *amount && after.units + 1 >= amount.units, "{} {} -> {}", token, amount, after);
+        }
+
+        // only the copies were closed
+        assert_eq!(users[0].lp_positions.len(), 1);
+        assert_eq!(venues[1].strategies().len(), 1);
+        assert_eq!(auction.orders().len(), 1);
+    }
+
+    #[test]
+    fn saved_db_has_nothing_left_in_the_market() {
+
+        let file = TempFile::new("save-with-market");
+        let mut config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(std::mem::take(&mut config.exchanges));
+
+        let mut user_db = UserDatabase::open(&file.0, &config.tokens).unwrap();
+        user_db.add_user(User::new(Network::Ethereum, "0xlp".to_string(), &config.tokens));
+        user_db.users[0].add_balance(Network::Ethereum, &token("USDC"), TokenAmount::whole(1_000, 6).unwrap()).unwrap();
+        user_db.users[0].add_balance(Network::Ethereum, &token("USDT"), TokenAmount::whole(1_000, 6).unwrap()).unwrap();
+        let usdc_before = user_db.users[0].balance(Network::Ethereum, &token("USDC")).unwrap();
+
+        add_liquidity(&mut venues, 0, &token("USDC"), &token("USDT"), TokenAmount::whole(1_000, 6).unwrap(), &mut user_db.users[0]).unwrap();
+        user_db.block = 7;
+        user_db.save_with_market(&venues, &[]).unwrap();
+
+        let reopened = UserDatabase::open(&file.0, &config.tokens).unwrap();
+        let usdc_after = reopened.users[0].balance(Network::Ethereum, &token("USDC")).unwrap();
+
+        assert_eq!(reopened.block, 7);
+        assert!(usdc_after.units + 1 >= usdc_before.units);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::SwapError;
+use crate::token::{TokenAmount, TokenType};
+use crate::user::User;
+use crate::venue::Venue;
+
+// Carbon style maker strategies. A strategy is two linked orders on one pair, one selling the
+// base token and one buying it back, each along its own price range. Takers fill an order
+// along a bonding curve from the best end of its range to the worst, and whatever an order
+// takes in becomes liquidity for the other one, so a strategy keeps trading back and forth
+// without the maker touching it.
+//
+// The curve is Carbon's; with the order's balance y, its capacity z and A, B from the square
+// roots of the range, the rate is (A * y / z + B)^2 and a taker paying x in gets
+// x * (A * y + B * z)^2 / (A * x * (A * y + B * z) + z^2) out. Rates are in base units of the
+// token the order sells per base unit of the token it takes in.
+
+//what a maker asks for when placing a strategy, prices are quote per base in whole tokens
+#[derive(Debug, Clone)]
+pub struct StrategyParams {
+
+    pub base: TokenType,
+    pub quote: TokenType,
+    pub sell_amount: TokenAmount,   //base put up for sale, may be zero for a buy only strategy
+    pub sell_low: f64,              //the first base is sold at sell_low, the last at sell_high
+    pub sell_high: f64,
+    pub buy_amount: TokenAmount,    //quote put up to buy base with, may be zero for a sell only strategy
+    pub buy_low: f64,               //the first base is bought at buy_high, the last at buy_low
+    pub buy_high: f64,
+}
+
+//one side of a strategy, sells one token of the pair for the other
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyOrder {
+
+    pub amount: TokenAmount,   //left to sell, proceeds of the other order add to it
+    pub low_price: f64,        //range as the maker set it, quote per base
+    pub high_price: f64,
+    capacity: u128,            //the most the order has held, where its rate is at the best end
+    a: f64,
+    b: f64,
+}
+
+//two linked orders placed by one maker on one exchange
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strategy {
+
+    pub id: u64,
+    pub owner: String,         //wallet address of the maker
+    pub base: TokenType,
+    pub quote: TokenType,
+    pub sell: StrategyOrder,   //sells base for quote
+    pub buy: StrategyOrder,    //sells quote for base
+}
+
+impl StrategyOrder {
+
+    //an order along rates from low_rate to high_rate, it starts full at high_rate
+    fn new(amount: TokenAmount, low_rate: f64, high_rate: f64, low_price: f64, high_price: f64) -> Self {
+
+        StrategyOrder {
+            amount,
+            low_price,
+            high_price,
+            capacity: amount.units,
+            a: high_rate.sqrt() - low_rate.sqrt(),
+            b: low_rate.sqrt(),
+        }
+    }
+
+    //rate the next unit taken in is paid at, 0 once the order is empty
+    pub fn marginal_rate(&self) -> f64 {
+
+        if self.amount.is_zero() {
+            return 0.0;
+        }
+
+        let rate = self.a * self.amount.units as f64 / self.capacity as f64 + self.b;
+        rate * rate
+    }
+
+    //what the order pays out for amount_in, never more than it holds
+    pub fn amount_out(&self, amount_in: u128) -> u128 {
+
+        if self.amount.is_zero() || amount_in == 0 {
+            return 0;
+        }
+
+        let (x, y, z) = (amount_in as f64, self.amount.units as f64, self.capacity as f64);
+        let t = self.a * y + self.b * z;
+
+        let out = (x * t * t / (self.a * x * t + z * z)).floor() as u128;
+        out.min(self.amount.units)
+    }
+
+    //what it takes in to empty the order, rounded up
+    pub fn amount_to_empty(&self) -> u128 {
+
+        if self.amount.is_zero() {
+            return 0;
+        }
+
+        let (y, z) = (self.amount.units as f64, self.capacity as f64);
+        (y * z / (self.b * (self.a * y + self.b * z))).ceil() as u128
+    }
+
+    fn take(&mut self, amount_out: u128) -> Result<(), SwapError> {
+
+        self.amount = self.amount.checked_sub(TokenAmount::from_units(amount_out, self.amount.decimals))?;
+        Ok(())
+    }
+
+    //proceeds of the linked order, past the capacity they move the best end of the curve up
+    fn deposit(&mut self, amount_in: u128) -> Result<(), SwapError> {
+
+        self.amount = self.amount.checked_add(TokenAmount::from_units(amount_in, self.amount.decimals))?;
+        self.capacity = self.capacity.max(self.amount.units);
+        Ok(())
+    }
+}
+
+impl Strategy {
+
+    pub fn new(id: u64, owner: &str, params: &StrategyParams) -> Result<Self, SwapError> {
+
+        let valid_range = |low: f64, high: f64| low > 0.0 && high >= low && high.is_finite();
+
+        if params.base == params.quote
+            || !valid_range(params.sell_low, params.sell_high)
+            || !valid_range(params.buy_low, params.buy_high)
+            || (params.sell_amount.is_zero() && params.buy_amount.is_zero()) {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        // a strategy buying above where it sells could be traded against itself
+        if params.buy_high > params.sell_low {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        // from whole token prices to base unit rates of each order
+        let scale = 10f64.powi(params.buy_amount.decimals as i32 - params.sell_amount.decimals as i32);
+
+        Ok(Strategy {
+            id,
+            owner: owner.to_string(),
+            base: params.base.clone(),
+            quote: params.quote.clone(),
+            sell: StrategyOrder::new(params.sell_amount, 1.0 / (params.sell_high * scale), 1.0 / (params.sell_low * scale), params.sell_low, params.sell_high),
+            buy: StrategyOrder::new(params.buy_amount, params.buy_low * scale, params.buy_high * scale, params.buy_low, params.buy_high),
+        })
+    }
+
+    //the order a taker swapping from -> to fills
+    pub fn order_for(&self, from: &TokenType, to: &TokenType) -> Option<&StrategyOrder> {
+
+        if from == &self.quote && to == &self.base {
+            Some(&self.sell)
+        } else if from == &self.base && to == &self.quote {
+            Some(&self.buy)
+        } else {
+            None
+        }
+    }
+
+    //Fill the order for from -> to, returns what it paid out. What the taker paid in goes to
+    //the other order.
+    pub fn fill(&mut self, from: &TokenType, to: &TokenType, amount_in: u128) -> Result<u128, SwapError> {
+
+        let (filled, linked) = if from == &self.quote && to == &self.base {
+            (&mut self.sell, &mut self.buy)
+        } else if from == &self.base && to == &self.quote {
+            (&mut self.buy, &mut self.sell)
+        } else {
+            return Err(SwapError::TokenPairNotSupported);
+        };
+
+        let amount_out = filled.amount_out(amount_in);
+
+        filled.take(amount_out)?;
+        linked.deposit(amount_in)?;
+
+        Ok(amount_out)
+    }
+}
+
+//Place a strategy on a venue on the user's network. Both orders' amounts leave the user's
+//balance and stay in the strategy until it's withdrawn.
+pub fn place_strategy(venues: &mut [Box<dyn Venue>], venue_index: usize, params: &StrategyParams, user: &mut User) -> Result<Strategy, SwapError> {
+
+    let venue = venues.get_mut(venue_index).ok_or(SwapError::StrategiesNotSupported)?;
+
+    if venue.network() != user.network {
+        return Err(SwapError::IncorrectNetwork);
+    }
+
+    if !user.has_sufficient_balance(user.network, &params.base, params.sell_amount)
+        || !user.has_sufficient_balance(user.network, &params.quote, params.buy_amount) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let strategy = venue.create_strategy(&user.wallet_address, params)?;
+
+    user.deduct_balance(user.network, &params.base, params.sell_amount)?;
+    user.deduct_balance(user.network, &params.quote, params.buy_amount)?;
+
+    Ok(strategy)
+}
+
+//Close one of the user's strategies and credit what both orders hold now, on the network of
+//the venue it was placed on.
+pub fn withdraw_strategy(venues: &mut [Box<dyn Venue>], venue_index: usize, strategy_id: u64, user: &mut User) -> Result<Strategy, SwapError> {
+
+    let venue = venues.get_mut(venue_index).ok_or(SwapError::StrategyNotFound)?;
+    let strategy = venue.withdraw_strategy(&user.wallet_address, strategy_id)?;
+    let network = venue.network();
+
+    user.add_balance(network, &strategy.base, strategy.sell.amount)?;
+    user.add_balance(network, &strategy.quote, strategy.buy.amount)?;
+
+    Ok(strategy)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    //a strategy on WETH against USDC selling WETH between sell_low and sell_high, buying it between 1800 and 1900
+    fn weth_strategy(sell_low: f64, sell_high: f64) -> Strategy {
+
+        Strategy::new(1, "0xmaker", &StrategyParams {
+            base: TokenType::new("WETH"),
+            quote: TokenType::new("USDC"),
+            sell_amount: TokenAmount::whole(10, 18).unwrap(),
+            sell_low,
+            sell_high,
+            buy_amount: TokenAmount::whole(20_000, 6).unwrap(),
+            buy_low: 1800.0,
+            buy_high: 1900.0,
+        }).unwrap()
+    }
+
+    #[test]
+    fn flat_range_pays_one_rate() {
+
+        // selling at exactly 4 USDC a USDT, a quarter of a USDT per USDC all the way down
+        let strategy = Strategy::new(1, "0xmaker", &StrategyParams {
+            base: TokenType::new("USDT"),
+            quote: TokenType::new("USDC"),
+            sell_amount: TokenAmount::whole(1_000, 6).unwrap(),
+            sell_low: 4.0,
+            sell_high: 4.0,
+            buy_amount: TokenAmount::zero(6),
+            buy_low: 1.0,
+            buy_high: 1.0,
+        }).unwrap();
+        let order = &strategy.sell;
+
+        assert_eq!(order.marginal_rate(), 0.25);
+        assert_eq!(order.amount_out(4_000), 1_000);
+        assert_eq!(order.amount_out(4_000_000_000), 1_000_000_000);
+        assert_eq!(order.amount_to_empty(), 4_000_000_000);
+    }
+
+    #[test]
+    fn rates_are_in_what_each_order_sells_per_unit_taken_in() {
+
+        let strategy = weth_strategy(2000.0, 2100.0);
+
+        // USDC -> WETH fills the sell order, starting at 1 WETH per 2000 USDC in base units
+        let sell_rate = 1e18 / 2000.0 / 1e6;
+        assert!(std::ptr::eq(strategy.order_for(&TokenType::new("USDC"), &TokenType::new("WETH")).unwrap(), &strategy.sell));
+        assert!((strategy.sell.marginal_rate() / sell_rate - 1.0).abs() < 1e-9);
+
+        // WETH -> USDC fills the buy order, starting at 1900 USDC per WETH
+        let buy_rate = 1900.0 * 1e6 / 1e18;
+        assert!(std::ptr::eq(strategy.order_for(&TokenType::new("WETH"), &TokenType::new("USDC")).unwrap(), &strategy.buy));
+        assert!((strategy.buy.marginal_rate() / buy_rate - 1.0).abs() < 1e-9);
+
+        assert!(strategy.order_for(&TokenType::new("WETH"), &TokenType::new("DAI")).is_none());
+    }
+
+    #[test]
+    fn rate_gets_worse_along_the_range() {
+
+        let mut strategy = weth_strategy(2000.0, 2100.0);
+        let (usdc, weth) = (TokenType::new("USDC"), TokenType::new("WETH"));
+        let first = strategy.sell.marginal_rate();
+
+        // the first 1_000 USDC buys more WETH than the next 1_000
+        let thousand = TokenAmount::whole(1_000, 6).unwrap().units;
+        let first_out = strategy.fill(&usdc, &weth, thousand).unwrap();
+        let second_out = strategy.fill(&usdc, &weth, thousand).unwrap();
+
+        assert!(second_out < first_out);
+        assert!(strategy.sell.marginal_rate() < first);
+        assert!(strategy.sell.marginal_rate() > 1e18 / 2100.0 / 1e6);
+    }
+
+    #[test]
+    fn draining_an_order_pays_out_all_of_it() {
+
+        let mut strategy = weth_strategy(2000.0, 2100.0);
+        let (usdc, weth) = (TokenType::new("USDC"), TokenType::new("WETH"));
+        let held = strategy.sell.amount;
+        let to_empty = strategy.sell.amount_to_empty();
+
+        // about 10 WETH between 2000 and 2100, and never more than the order holds
+        assert!(to_empty > TokenAmount::whole(20_000, 6).unwrap().units && to_empty < TokenAmount::whole(21_000, 6).unwrap().units);
+        assert!(strategy.sell.amount_out(to_empty - 1_000_000) < held.units);
+        assert_eq!(strategy.sell.amount_out(to_empty * 2), held.units);
+
+        // what the taker paid goes to the buy order
+        let buy_before = strategy.buy.amount;
+        assert_eq!(strategy.fill(&usdc, &weth, to_empty).unwrap(), held.units);
+        assert!(strategy.sell.amount.is_zero());
+        assert_eq!(strategy.sell.marginal_rate(), 0.0);
+        assert_eq!(strategy.sell.amount_out(1_000), 0);
+        assert_eq!(strategy.buy.amount.units, buy_before.units + to_empty);
+
+        assert!(matches!(strategy.fill(&weth, &TokenType::new("DAI"), 1), Err(SwapError::TokenPairNotSupported)));
+    }
+}
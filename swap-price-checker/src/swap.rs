@@ -0,0 +1,618 @@
+use crate::aggregator::Aggregator;
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::gas::GasModel;
+use crate::pricing::SwapQuote;
+use crate::router::{Router, SwapLeg, find_best_split, net_units, order_gas_units, order_quote, route_quote};
+use crate::token::{Token, TokenAmount, TokenType, mul_div};
+use crate::user::UserDatabase;
+use crate::venue::{StagedVenues, Venue, VenueFees};
+
+//slippage tolerance used when the user doesn't pick one, 0.5%
+pub const DEFAULT_SLIPPAGE_TOLERANCE: f64 = 0.005;
+
+//when user decides to swap
+pub struct Swap {
+
+    pub user_wallet_address: String,
+    pub from_token: Token,
+    pub to_token: Token,
+    pub amount: TokenAmount,      //amount of from_token to swap
+    pub max_slippage: f64,   //revert if the swap slips more than this, 0.005 = 0.5%
+
+}
+
+pub struct SwapResult {
+
+    pub legs: Vec<SwapLeg>,      //one leg unless the order was split across venues
+    pub received_amount: TokenAmount,
+    pub spot_price: f64,
+    pub execution_price: f64,
+    pub price_impact_bps: f64,   //how far the swap moves the pool price, in basis points
+    pub slippage: f64,           //shortfall of the execution price against the quoted spot price, 0.01 = 1%
+    pub gas_units: u64,          //gas of every hop on every leg
+    pub gas_cost: TokenAmount,   //gas cost in the token received, what routes are ranked on
+    pub gas_cost_usd: f64,
+    pub fees: Vec<VenueFees>,    //fees paid on every hop, in the token going into the hop, and the aggregator's in the token received
+    pub aggregator: Option<String>,   //the aggregator the swap went through, None if it went direct
+    pub options: Vec<SwapOption>,     //going direct and through every aggregator, as quoted
+}
+
+//one way the swap could have gone, compared on what it pays out after gas
+#[derive(Debug, Clone)]
+pub struct SwapOption {
+
+    pub name: String,                 //"direct" or the aggregator's name
+    pub received_amount: TokenAmount, //after any aggregator fee
+    pub gas_units: u64,
+    pub net_amount: TokenAmount,      //received_amount less gas, what the options are ranked on
+}
+
+//the best way to do a swap, worked out before anything is executed
+#[derive(Debug, Clone)]
+pub struct SwapPlan {
+
+    pub legs: Vec<SwapLeg>,
+    pub quote: SwapQuote,             //after any aggregator fee
+    pub gas_units: u64,
+    pub aggregator: Option<usize>,    //index of the aggregator it goes through, None if it goes direct
+    pub options: Vec<SwapOption>,     //going direct and through every aggregator, as quoted
+}
+
+//Quote a swap on a network without executing it. Finds the best single route across the
+//venues (direct or multi-hop) and the best way to split the order across them, keeps
+//whichever pays more once gas is paid, then compares that with every aggregator on the
+//network. A split pays gas on every leg, so it has to make up for that.
+pub fn plan_swap(venues: &[Box<dyn Venue>], aggregators: &[Aggregator], gas: &GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<SwapPlan, SwapError> {
+
+    if amount.is_zero() {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    let single_route = Router::find_best_route(venues, gas, network, from, to, amount).and_then(|route| {
+        let quote = route_quote(&route)?;
+        let gas_units = route.iter().map(|hop| hop.gas_units).sum();
+        Ok(vec![SwapLeg { route, amount_in: amount, quote, gas_units }])
+    });
+    let split = find_best_split(venues, gas, network, from, to, amount);
+
+    let direct = match (single_route, split) {
+        (Ok(single_route), Ok(split)) => {
+            let split_net = net_units(gas, network, order_gas_units(&split), to, order_quote(&split)?.received_amount);
+            let single_route_net = net_units(gas, network, order_gas_units(&single_route), to, order_quote(&single_route)?.received_amount);
+
+            if split_net > single_route_net {
+                Ok(split)
+            } else {
+                Ok(single_route)
+            }
+        },
+        (Ok(single_route), Err(_)) => Ok(single_route),
+        (Err(_), Ok(split)) => Ok(split),
+        (Err(error), Err(_)) => Err(error),
+    };
+
+    // Then ask every aggregator on the network. It may find a better way through the same
+    // venues, but it has to make up for its fee and its own gas to be picked.
+    let mut options = Vec::new();
+    let mut best: Option<(Option<usize>, Vec<SwapLeg>, SwapQuote, u64)> = None;
+
+    let mut candidates = Vec::new();
+    match direct {
+        Ok(legs) => {
+            let quote = order_quote(&legs)?;
+            let gas_units = order_gas_units(&legs);
+            candidates.push((None, legs, quote, gas_units));
+        },
+        Err(error) if !aggregators.iter().any(|aggregator| aggregator.network == network) => return Err(error),
+        Err(_) => {},
+    }
+
+    for (index, aggregator) in aggregators.iter().enumerate().filter(|(_, aggregator)| aggregator.network == network) {
+        if let Ok(aggregated) = aggregator.quote(venues, gas, from, to, amount) {
+            candidates.push((Some(index), aggregated.legs, aggregated.quote, aggregated.gas_units));
+        }
+    }
+
+    for (aggregator_index, legs, quote, gas_units) in candidates {
+        let net = net_units(gas, network, gas_units, to, quote.received_amount);
+
+        options.push(SwapOption {
+            name: aggregator_index.map_or("direct", |index| aggregators[index].name()).to_string(),
+            received_amount: quote.received_amount,
+            gas_units,
+            net_amount: TokenAmount::from_units(net, quote.received_amount.decimals),
+        });
+
+        let is_better = match &best {
+            Some((_, _, best_quote, best_gas_units)) => net > net_units(gas, network, *best_gas_units, to, best_quote.received_amount),
+            None => true,
+        };
+
+        if is_better {
+            best = Some((aggregator_index, legs, quote, gas_units));
+        }
+    }
+
+    let (aggregator, legs, quote, gas_units) = best.ok_or(SwapError::TokenPairNotSupported)?;
+
+    Ok(SwapPlan { legs, quote, gas_units, aggregator, options })
+}
+
+//one exchange's or aggregator's answer in a quote comparison
+#[derive(Debug)]
+pub struct VenueQuote {
+
+    pub venue_name: String,
+    pub quote: Result<SwapQuote, SwapError>,   //why it can't do the swap, e.g. NotEnoughLiquidity
+    pub liquidity: Option<TokenAmount>,        //to_token it could pay out, None for aggregators
+    pub gas_units: u64,
+    pub net_amount: Option<TokenAmount>,       //received less gas, what the quotes are ranked on
+}
+
+//Quote a swap on every exchange and aggregator on a network, without executing it. Each
+//exchange is asked for its own direct pool only, so unlike `plan_swap` nothing is routed or
+//split. Best first by what they pay after gas, the ones that can't do the swap last.
+pub fn compare_quotes(venues: &[Box<dyn Venue>], aggregators: &[Aggregator], gas: &GasModel, network: Network, from: &TokenType, to: &TokenType, amount: TokenAmount) -> Result<Vec<VenueQuote>, SwapError> {
+
+    if amount.is_zero() {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    let mut quotes: Vec<VenueQuote> = venues.iter()
+        .filter(|venue| venue.network() == network)
+        .map(|venue| VenueQuote {
+            venue_name: venue.name().to_string(),
+            quote: venue.quote(from, to, amount),
+            liquidity: venue.liquidity(from, to),
+            gas_units: venue.gas_units(),
+            net_amount: None,
+        })
+        .collect();
+
+    for aggregator in aggregators.iter().filter(|aggregator| aggregator.network == network) {
+        let quoted = aggregator.quote(venues, gas, from, to, amount);
+
+        quotes.push(VenueQuote {
+            venue_name: aggregator.name().to_string(),
+            gas_units: quoted.as_ref().map_or(aggregator.gas_units, |quoted| quoted.gas_units),
+            quote: quoted.map(|quoted| quoted.quote),
+            liquidity: None,
+            net_amount: None,
+        });
+    }
+
+    for venue_quote in &mut quotes {
+        if let Ok(quote) = &venue_quote.quote {
+            let net = net_units(gas, network, venue_quote.gas_units, to, quote.received_amount);
+            venue_quote.net_amount = Some(TokenAmount::from_units(net, quote.received_amount.decimals));
+        }
+    }
+
+    // None sorts before Some, so the failed quotes end up last
+    quotes.sort_by_key(|venue_quote| std::cmp::Reverse(venue_quote.net_amount.map(|net| net.units)));
+
+    Ok(quotes)
+}
+
+//Logic for performing a swap
+pub fn perform_swap(swap: &Swap, venues: &mut [Box<dyn Venue>], aggregators: &mut [Aggregator], gas: &GasModel, user_db: &mut UserDatabase) -> Result<SwapResult, SwapError> {
+    // 1. Fetch the user
+    let user = match user_db.get_user_by_address_mut(&swap.user_wallet_address) {
+        Some(u) => u,
+        None => return Err(SwapError::UserNotFound),
+    };
+
+    // 2. Ensure the amount makes sense and the user has enough balance for the swap
+    if swap.amount.is_zero() {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    if !user.has_sufficient_balance(user.network, &swap.from_token.token_type, swap.amount) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    // 3. Find the best way to do the swap on the user's network
+    let network = user.network;
+    let from = &swap.from_token.token_type;
+    let to = &swap.to_token.token_type;
+
+    let SwapPlan { legs, quote, gas_units, aggregator: aggregator_index, options } = plan_swap(venues, aggregators, gas, network, from, to, swap.amount)?;
+
+    // Revert before touching balances if even the best price slips past the user's tolerance
+    if quote.slippage() > swap.max_slippage {
+        return Err(SwapError::SlippageExceeded);
+    }
+
+    // 4. Proceed with the swap. Every leg was quoted against the current venues, so the whole
+    //    order is executed first and then settled in one go against the user's balance.
+    //    The tokens go into each venue on every leg and come out of it, so the next quote sees
+    //    the new liquidity. Each hop passes on what the previous one actually paid out.
+    //    It all runs on copies of the venues, the aggregator and the user, which only replace
+    //    them once nothing can fail any more, so a failing hop leaves the swap undone.
+    let mut staged = StagedVenues::new();
+    let mut received_amount = TokenAmount::zero(quote.received_amount.decimals);
+    let mut fees = Vec::new();
+
+    for leg in &legs {
+        let mut amount = leg.amount_in;
+
+        for hop in &leg.route {
+            let executed = staged.execute(venues, hop.venue_index, &hop.from_token, &hop.to_token, amount)?;
+
+            fees.push(VenueFees {
+                venue_name: hop.venue_name.clone(),
+                token: hop.from_token.clone(),
+                lp_fee: executed.lp_fee,
+                protocol_fee: executed.protocol_fee,
+            });
+
+            amount = executed.received_amount;
+        }
+
+        received_amount = received_amount.checked_add(amount)?;
+    }
+
+    // the aggregator takes its fee out of what the venues actually paid
+    let mut charged_aggregator = None;
+
+    if let Some(index) = aggregator_index {
+        let mut aggregator = aggregators[index].clone();
+        let fee = aggregator.fee_on(received_amount)?;
+
+        received_amount = received_amount.checked_sub(fee)?;
+        aggregator.collect_fee(to, fee)?;
+
+        fees.push(VenueFees {
+            venue_name: aggregator.name().to_string(),
+            token: to.clone(),
+            lp_fee: TokenAmount::zero(fee.decimals),
+            protocol_fee: fee,
+        });
+
+        charged_aggregator = Some((index, aggregator));
+    }
+
+    // The fills can come out worse than the quote, e.g. two legs through a shared pool. What
+    // was actually received has to be within the tolerance of the quote as well.
+    let tolerance_ppm = ((1.0 - swap.max_slippage.clamp(0.0, 1.0)) * 1_000_000.0).round() as u128;
+    if received_amount.units < mul_div(quote.received_amount.units, tolerance_ppm, 1_000_000)? {
+        return Err(SwapError::SlippageExceeded);
+    }
+
+    let mut settled = user.clone();
+    settled.deduct_balance(network, from, swap.amount)?;
+    settled.add_balance(network, to, received_amount)?;
+
+    // nothing below can fail, the swap goes through as a whole
+    staged.commit(venues);
+    *user = settled;
+
+    if let Some((index, aggregator)) = charged_aggregator {
+        aggregators[index] = aggregator;
+    }
+
+    // gas is paid in the network's native token, which the mock doesn't hold, so it's
+    // reported next to the swap rather than taken off the balance
+    let gas_cost = gas.cost_in(network, gas_units, to, received_amount.decimals)
+        .unwrap_or(TokenAmount::zero(received_amount.decimals));
+
+    Ok(SwapResult {
+        legs,
+        received_amount,
+        spot_price: quote.spot_price,
+        execution_price: quote.execution_price,
+        price_impact_bps: quote.price_impact_bps(),
+        slippage: quote.slippage(),
+        gas_units,
+        gas_cost,
+        gas_cost_usd: gas.cost_usd(network, gas_units),
+        fees,
+        aggregator: aggregator_index.map(|index| aggregators[index].name().to_string()),
+        options,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::parse_toml;
+    use crate::exchange::ExchangeData;
+    use crate::user::User;
+
+    const CONFIG: &str = r#"
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "DAI"
+        name = "Dai"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    // Three exchanges quoting USDC -> USDT with gas at $9 per 150_000 units. Uniswap's deep
+    // pool pays the most but burns four times the gas, PancakeSwap has no USDT pool.
+    const QUOTES_CONFIG: &str = r#"
+        [[networks]]
+        name = "Ethereum"
+        native_token = "ETH"
+        native_usd_price = 3000.0
+        gas_price_gwei = 20.0
+
+        [[tokens]]
+        symbol = "USDC"
+        name = "USD Coin"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "USDT"
+        name = "Tether"
+        decimals = 6
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[tokens]]
+        symbol = "DAI"
+        name = "Dai"
+        decimals = 18
+        network = "Ethereum"
+        usd_price = 1.0
+
+        [[exchanges]]
+        name = "PancakeSwap"
+        network = "Ethereum"
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "DAI"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Uniswap"
+        network = "Ethereum"
+        gas_units = 600000
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "1000000"
+        to_reserve = "1000000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+
+        [[exchanges]]
+        name = "Sushi"
+        network = "Ethereum"
+        gas_units = 100000
+
+        [[exchanges.pairs]]
+        from = "USDC"
+        to = "USDT"
+        from_reserve = "100000"
+        to_reserve = "100000"
+        fee_bps = 30
+        pricing = { model = "constant_product" }
+    "#;
+
+    //quotes USDT -> DAI at par but can't execute, the second hop of USDC -> USDT -> DAI
+    #[derive(Clone)]
+    struct BrokenVenue;
+
+    impl Venue for BrokenVenue {
+
+        fn name(&self) -> &str {
+
+            "Broken"
+        }
+
+        fn network(&self) -> Network {
+
+            Network::Ethereum
+        }
+
+        fn supported_pairs(&self) -> Vec<(TokenType, TokenType)> {
+
+            vec![(TokenType::new("USDT"), TokenType::new("DAI"))]
+        }
+
+        fn quote(&self, _from: &TokenType, _to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            Ok(SwapQuote {
+                received_amount: amount_in.rescale(18)?,
+                spot_price: 1.0,
+                execution_price: 1.0,
+                price_after: 1.0,
+                lp_fee: TokenAmount::zero(amount_in.decimals),
+                protocol_fee: TokenAmount::zero(amount_in.decimals),
+            })
+        }
+
+        fn execute(&mut self, _from: &TokenType, _to: &TokenType, _amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            Err(SwapError::NotEnoughLiquidity)
+        }
+    }
+
+    //quotes USDT -> DAI at par but only pays out half of it
+    #[derive(Clone)]
+    struct ShortVenue;
+
+    impl Venue for ShortVenue {
+
+        fn name(&self) -> &str {
+
+            "Short"
+        }
+
+        fn network(&self) -> Network {
+
+            Network::Ethereum
+        }
+
+        fn supported_pairs(&self) -> Vec<(TokenType, TokenType)> {
+
+            BrokenVenue.supported_pairs()
+        }
+
+        fn quote(&self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            BrokenVenue.quote(from, to, amount_in)
+        }
+
+        fn execute(&mut self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+            let quote = self.quote(from, to, amount_in)?;
+            Ok(SwapQuote { received_amount: TokenAmount::from_units(quote.received_amount.units / 2, 18), ..quote })
+        }
+    }
+
+    fn swap(amount: &str) -> Swap {
+
+        Swap {
+            user_wallet_address: "0xtest".to_string(),
+            from_token: Token { token_type: TokenType::new("USDC") },
+            to_token: Token { token_type: TokenType::new("DAI") },
+            amount: TokenAmount::parse(amount, 6).unwrap(),
+            max_slippage: 1.0,
+        }
+    }
+
+    #[test]
+    fn quotes_are_ranked_on_what_they_pay_after_gas() {
+
+        let config = parse_toml(QUOTES_CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+
+        let quotes = compare_quotes(&venues, &[], &config.gas, Network::Ethereum, &usdc, &usdt, TokenAmount::whole(1000, 6).unwrap()).unwrap();
+        let names: Vec<&str> = quotes.iter().map(|venue_quote| venue_quote.venue_name.as_str()).collect();
+        assert_eq!(names, ["Sushi", "Uniswap", "PancakeSwap"]);
+
+        // Uniswap pays more before gas and loses it all to gas
+        let received = |index: usize| quotes[index].quote.as_ref().unwrap().received_amount;
+        assert!(received(1) > received(0));
+        assert!(quotes[0].net_amount.unwrap() > quotes[1].net_amount.unwrap());
+
+        let gas_cost = config.gas.cost_in(Network::Ethereum, 600_000, &usdt, 6).unwrap();
+        assert_eq!(quotes[1].net_amount, Some(received(1).checked_sub(gas_cost).unwrap()));
+    }
+
+    #[test]
+    fn failed_quotes_sort_last() {
+
+        let config = parse_toml(QUOTES_CONFIG).unwrap();
+        let venues = ExchangeData::into_venues(config.exchanges);
+        let (usdc, usdt) = (TokenType::new("USDC"), TokenType::new("USDT"));
+
+        // PancakeSwap is listed first and can't do the swap, so it comes after both pools
+        let quotes = compare_quotes(&venues, &[], &config.gas, Network::Ethereum, &usdc, &usdt, TokenAmount::whole(1000, 6).unwrap()).unwrap();
+        let failed = quotes.last().unwrap();
+
+        assert_eq!(failed.venue_name, "PancakeSwap");
+        assert!(matches!(failed.quote, Err(SwapError::TokenPairNotSupported)));
+        assert_eq!(failed.net_amount, None);
+        assert!(quotes[..2].iter().all(|venue_quote| venue_quote.quote.is_ok()));
+
+        // the amount is checked before anything is quoted
+        assert!(matches!(compare_quotes(&venues, &[], &config.gas, Network::Ethereum, &usdc, &usdt, TokenAmount::zero(6)), Err(SwapError::InvalidAmount)));
+    }
+
+    #[test]
+    fn failing_hop_leaves_venues_and_balances_untouched() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+        venues.push(Box::new(BrokenVenue));
+
+        let mut user_db = UserDatabase::new_db();
+        user_db.add_user(User::new(Network::Ethereum, "0xtest".to_string(), &config.tokens));
+        let balances_before = user_db.users[0].balances.clone();
+
+        let usdc = TokenType::new("USDC");
+        let usdt = TokenType::new("USDT");
+        let quote_before = venues[0].quote(&usdc, &usdt, TokenAmount::whole(1, 6).unwrap()).unwrap().received_amount;
+
+        let result = perform_swap(&swap("1"), &mut venues, &mut [], &config.gas, &mut user_db);
+
+        assert!(matches!(result, Err(SwapError::NotEnoughLiquidity)));
+        assert_eq!(venues[0].quote(&usdc, &usdt, TokenAmount::whole(1, 6).unwrap()).unwrap().received_amount, quote_before);
+        assert_eq!(user_db.users[0].balances, balances_before);
+    }
+
+    #[test]
+    fn fill_worse_than_the_quote_is_slippage() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+        venues.push(Box::new(ShortVenue));
+
+        let mut user_db = UserDatabase::new_db();
+        user_db.add_user(User::new(Network::Ethereum, "0xtest".to_string(), &config.tokens));
+        user_db.users[0].add_balance(Network::Ethereum, &TokenType::new("USDC"), TokenAmount::whole(1, 6).unwrap()).unwrap();
+        let balances_before = user_db.users[0].balances.clone();
+
+        // the quote is within any tolerance, the fill pays half of it
+        let result = perform_swap(&Swap { max_slippage: 0.1, ..swap("1") }, &mut venues, &mut [], &config.gas, &mut user_db);
+
+        assert!(matches!(result, Err(SwapError::SlippageExceeded)));
+        assert_eq!(user_db.users[0].balances, balances_before);
+    }
+
+    #[test]
+    fn swap_moves_pool_and_balances() {
+
+        let config = parse_toml(CONFIG).unwrap();
+        let mut venues = ExchangeData::into_venues(config.exchanges);
+
+        let mut user_db = UserDatabase::new_db();
+        user_db.add_user(User::new(Network::Ethereum, "0xtest".to_string(), &config.tokens));
+
+        let usdc = TokenType::new("USDC");
+        let usdt = TokenType::new("USDT");
+        user_db.users[0].add_balance(Network::Ethereum, &usdc, TokenAmount::whole(1000, 6).unwrap()).unwrap();
+        let usdc_before = user_db.users[0].balance(Network::Ethereum, &usdc).unwrap();
+        let usdt_before = user_db.users[0].balance(Network::Ethereum, &usdt).unwrap();
+
+        let swap = Swap { to_token: Token { token_type: usdt.clone() }, ..swap("1000") };
+        let result = perform_swap(&swap, &mut venues, &mut [], &config.gas, &mut user_db).unwrap();
+
+        let user = &user_db.users[0];
+        assert_eq!(user.balance(Network::Ethereum, &usdc).unwrap(), usdc_before.checked_sub(swap.amount).unwrap());
+        assert_eq!(user.balance(Network::Ethereum, &usdt).unwrap(), usdt_before.checked_add(result.received_amount).unwrap());
+
+        // the pool took the USDC in, so the next quote pays less
+        let next = venues[0].quote(&usdc, &usdt, swap.amount).unwrap();
+        assert!(next.received_amount < result.received_amount);
+    }
+}
@@ -0,0 +1,456 @@
+use std::fmt;
+use std::cmp::Ordering;
+
+use crate::error::SwapError;
+use crate::exchange::Network;
+
+//To represent our tokens, by symbol (USDC, WETH etc). What a symbol means on a
+//given network (name, decimals, contract) is looked up in the TokenRegistry.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
+pub struct TokenType(String);
+
+//An amount of a token in integer base units, e.g. 1.5 USDC (6 decimals) is 1_500_000 units.
+//Balances and reserves never go through floating point, so they don't pick up rounding errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct TokenAmount {
+
+    pub units: u128,
+    pub decimals: u8,
+}
+
+pub struct Token {
+
+    pub token_type: TokenType,
+}
+
+//everything we know about a token on one network
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+
+    pub symbol: TokenType,
+    pub name: String,                       //e.g. "USD Coin"
+    pub decimals: u8,                       //6 for USDC, 18 for most ERC-20s
+    pub network: Network,
+    pub contract_address: Option<String>,
+    pub usd_price: f64,                     //mock market price, used to price gas in the token
+}
+
+//The tokens the checker knows about. Drives the menus, random balances and pair validation.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+
+    tokens: Vec<TokenInfo>,
+}
+
+//implementing functionalities
+
+impl TokenType {
+
+    pub fn new(symbol: &str) -> Self {
+
+        TokenType(symbol.to_string())
+    }
+
+    pub fn symbol(&self) -> &str {
+
+        &self.0
+    }
+}
+
+impl fmt::Display for TokenType {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TokenRegistry {
+
+    pub fn new() -> Self {
+
+        TokenRegistry { tokens: Vec::new() }
+    }
+
+    //add a token, a symbol can only be registered once per network
+    pub fn register(&mut self, info: TokenInfo) -> Result<(), SwapError> {
+
+        if self.get(&info.symbol, info.network).is_some() {
+            return Err(SwapError::DuplicateToken);
+        }
+
+        self.tokens.push(info);
+        Ok(())
+    }
+
+    pub fn get(&self, symbol: &TokenType, network: Network) -> Option<&TokenInfo> {
+
+        self.tokens.iter().find(|info| &info.symbol == symbol && info.network == network)
+    }
+
+    //decimals of a token, or TokenNotSupported if it isn't registered on that network
+    pub fn decimals(&self, symbol: &TokenType, network: Network) -> Result<u8, SwapError> {
+
+        self.get(symbol, network).map(|info| info.decimals).ok_or(SwapError::TokenNotSupported)
+    }
+
+    //every token registered on a network, in the order they were registered
+    pub fn tokens_on(&self, network: Network) -> Vec<&TokenInfo> {
+
+        self.tokens.iter().filter(|info| info.network == network).collect()
+    }
+
+    pub fn tokens(&self) -> &[TokenInfo] {
+
+        &self.tokens
+    }
+}
+
+impl TokenAmount {
+
+    pub fn zero(decimals: u8) -> Self {
+
+        TokenAmount { units: 0, decimals }
+    }
+
+    pub fn from_units(units: u128, decimals: u8) -> Self {
+
+        TokenAmount { units, decimals }
+    }
+
+    //a whole number of tokens, e.g. 50 USDC, AmountOverflow if that many base units don't fit
+    pub fn whole(tokens: u64, decimals: u8) -> Result<Self, SwapError> {
+
+        let units = 10u128.checked_pow(decimals as u32)
+            .and_then(|scale| scale.checked_mul(tokens as u128))
+            .ok_or(SwapError::AmountOverflow)?;
+
+        Ok(TokenAmount { units, decimals })
+    }
+
+    //parse user input like "1500" or "1500.25" without going through f64
+    pub fn parse(input: &str, decimals: u8) -> Result<Self, SwapError> {
+
+        let (whole, fraction) = match input.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (input, ""),
+        };
+
+        // "" and "." are not amounts, and we can't hold more precision than the token has
+        if (whole.is_empty() && fraction.is_empty()) || fraction.len() > decimals as usize {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let mut units: u128 = 0;
+
+        // pad the fraction with zeros up to the token's decimals, "1.5" -> 1 500000
+        let padding = "0".repeat(decimals as usize - fraction.len());
+
+        for digit in whole.chars().chain(fraction.chars()).chain(padding.chars()) {
+            units = units.checked_mul(10)
+                .and_then(|units| units.checked_add(digit.to_digit(10).unwrap() as u128))
+                .ok_or(SwapError::AmountOverflow)?;
+        }
+
+        Ok(TokenAmount { units, decimals })
+    }
+
+    //Pool pricing curves work in f64, their output is rounded down to whole base units
+    //so a pool never pays out more than its maths says.
+    pub fn from_f64(value: f64, decimals: u8) -> Result<Self, SwapError> {
+
+        if !value.is_finite() || value < 0.0 {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let units = (value * 10f64.powi(decimals as i32)).floor();
+
+        if units >= u128::MAX as f64 {
+            return Err(SwapError::AmountOverflow);
+        }
+
+        Ok(TokenAmount { units: units as u128, decimals })
+    }
+
+    pub fn to_f64(self) -> f64 {
+
+        self.units as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    pub fn is_zero(self) -> bool {
+
+        self.units == 0
+    }
+
+    //the same amount in other base units, e.g. USDC moving from 6 to 18 decimals.
+    //Dropping decimals rounds down.
+    pub fn rescale(self, decimals: u8) -> Result<Self, SwapError> {
+
+        let units = if decimals >= self.decimals {
+            10u128.checked_pow((decimals - self.decimals) as u32)
+                .and_then(|scale| self.units.checked_mul(scale))
+                .ok_or(SwapError::AmountOverflow)?
+        } else {
+            self.units / 10u128.pow((self.decimals - decimals) as u32)
+        };
+
+        Ok(TokenAmount { units, decimals })
+    }
+
+    pub fn checked_add(self, other: TokenAmount) -> Result<Self, SwapError> {
+
+        if self.decimals != other.decimals {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let units = self.units.checked_add(other.units).ok_or(SwapError::AmountOverflow)?;
+        Ok(TokenAmount { units, decimals: self.decimals })
+    }
+
+    pub fn checked_sub(self, other: TokenAmount) -> Result<Self, SwapError> {
+
+        if self.decimals != other.decimals {
+            return Err(SwapError::InvalidAmount);
+        }
+
+        let units = self.units.checked_sub(other.units).ok_or(SwapError::AmountUnderflow)?;
+        Ok(TokenAmount { units, decimals: self.decimals })
+    }
+
+    //the amount times numerator / denominator rounded down, e.g. a share of it in basis points
+    pub fn mul_div(self, numerator: u128, denominator: u128) -> Result<Self, SwapError> {
+
+        Ok(TokenAmount { units: mul_div(self.units, numerator, denominator)?, decimals: self.decimals })
+    }
+}
+
+//a * b / c rounded down, exact even when a * b doesn't fit in a u128.
+//LP share maths multiplies two reserves' worth of base units, which easily overflows.
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> Result<u128, SwapError> {
+
+    mul_div_remainder(a, b, c).map(|(quotient, _)| quotient)
+}
+
+//a * b / c rounded up
+pub(crate) fn mul_div_up(a: u128, b: u128, c: u128) -> Result<u128, SwapError> {
+
+    let (quotient, remainder) = mul_div_remainder(a, b, c)?;
+
+    if remainder == 0 {
+        Ok(quotient)
+    } else {
+        quotient.checked_add(1).ok_or(SwapError::AmountOverflow)
+    }
+}
+
+fn mul_div_remainder(a: u128, b: u128, c: u128) -> Result<(u128, u128), SwapError> {
+
+    if c == 0 {
+        return Err(SwapError::InvalidAmount);
+    }
+
+    const LOW: u128 = u64::MAX as u128;
+
+    // 256 bit product as (high, low) from 64 bit halves
+    let (a_high, a_low) = (a >> 64, a & LOW);
+    let (b_high, b_low) = (b >> 64, b & LOW);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let middle = (low_low >> 64) + (low_high & LOW) + (high_low & LOW);
+
+    let low = (low_low & LOW) | ((middle & LOW) << 64);
+    let high = a_high * b_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+
+    // the quotient has to fit in a u128
+    if high >= c {
+        return Err(SwapError::AmountOverflow);
+    }
+
+    // long division one bit at a time, the remainder always stays below c
+    let mut remainder = high;
+    let mut quotient: u128 = 0;
+
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+
+    Ok((quotient, remainder))
+}
+
+//amounts are only comparable when they are in the same base units
+impl PartialOrd for TokenAmount {
+
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+
+        if self.decimals == other.decimals {
+            Some(self.units.cmp(&other.units))
+        } else {
+            None
+        }
+    }
+}
+
+//prints the amount in whole tokens, 1_500_000 USDC units -> "1.5"
+impl fmt::Display for TokenAmount {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        let scale = 10u128.pow(self.decimals as u32);
+        let whole = self.units / scale;
+        let fraction = self.units % scale;
+
+        if fraction == 0 {
+            return write!(f, "{}", whole);
+        }
+
+        let fraction = format!("{:0width$}", fraction, width = self.decimals as usize);
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn mul_div_matches_plain_maths_when_it_fits() {
+
+        for (a, b, c) in [(7u128, 3, 4), (0, 5, 3), (1_000_000, 999_999, 7), (u64::MAX as u128, u64::MAX as u128, 3)] {
+            assert_eq!(mul_div(a, b, c).unwrap(), a * b / c);
+            assert_eq!(mul_div_remainder(a, b, c).unwrap(), (a * b / c, a * b % c));
+        }
+    }
+
+    #[test]
+    fn mul_div_is_exact_past_u128() {
+
+        // 10^60 doesn't fit in a u128, the quotient does
+        let e30 = 10u128.pow(30);
+        assert_eq!(mul_div(e30, e30, 10u128.pow(24)).unwrap(), 10u128.pow(36));
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, 2, 2).unwrap(), u128::MAX);
+        assert_eq!(mul_div_remainder(u128::MAX, 3, 2).map_err(|error| error.exit_code()), Err(SwapError::AmountOverflow.exit_code()));
+        assert_eq!(mul_div_remainder(u128::MAX, u128::MAX - 1, u128::MAX).unwrap(), (u128::MAX - 1, 0));
+    }
+
+    #[test]
+    fn mul_div_rounds_down_and_up() {
+
+        assert_eq!(mul_div(10, 10, 3).unwrap(), 33);
+        assert_eq!(mul_div_up(10, 10, 3).unwrap(), 34);
+
+        // no remainder, nothing to round
+        assert_eq!(mul_div_up(10, 9, 3).unwrap(), 30);
+        assert_eq!(mul_div_up(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+
+        // (2^128 - 1) * 2^127 / (2^127 + 1) is 2^128 - 3 and a bit, the product is past u128
+        let half = 1u128 << 127;
+        assert_eq!(mul_div(u128::MAX, half, half + 1).unwrap(), u128::MAX - 2);
+        assert_eq!(mul_div_up(u128::MAX, half, half + 1).unwrap(), u128::MAX - 1);
+    }
+
+    #[test]
+    fn mul_div_rejects_bad_input() {
+
+        assert!(matches!(mul_div(1, 1, 0), Err(SwapError::InvalidAmount)));
+        assert!(matches!(mul_div(u128::MAX, 2, 1), Err(SwapError::AmountOverflow)));
+        assert!(matches!(mul_div_up(u128::MAX, 3, 2), Err(SwapError::AmountOverflow)));
+    }
+
+    #[test]
+    fn amount_share_keeps_its_decimals() {
+
+        // 99.5% of a balance that times 9_950 would overflow a u128
+        let big = TokenAmount::from_units(u128::MAX / 1_000, 18);
+        let kept = big.mul_div(9_950, 10_000).unwrap();
+        assert_eq!(kept.units, mul_div(u128::MAX / 1_000, 9_950, 10_000).unwrap());
+        assert_eq!(kept.decimals, 18);
+        assert!(matches!(big.mul_div(1, 0), Err(SwapError::InvalidAmount)));
+    }
+
+    #[test]
+    fn parse_reads_whole_and_fractional_amounts() {
+
+        assert_eq!(TokenAmount::parse("1500", 6).unwrap(), TokenAmount::from_units(1_500_000_000, 6));
+        assert_eq!(TokenAmount::parse("1500.25", 6).unwrap(), TokenAmount::from_units(1_500_250_000, 6));
+        assert_eq!(TokenAmount::parse("0.000001", 6).unwrap(), TokenAmount::from_units(1, 6));
+        assert_eq!(TokenAmount::parse(".5", 6).unwrap(), TokenAmount::from_units(500_000, 6));
+        assert_eq!(TokenAmount::parse("5.", 6).unwrap(), TokenAmount::from_units(5_000_000, 6));
+        assert_eq!(TokenAmount::parse("007", 0).unwrap(), TokenAmount::from_units(7, 0));
+    }
+
+    #[test]
+    fn parse_rejects_what_isnt_an_amount() {
+
+        for input in ["", ".", "1.1234567", "-1", "+1", "1e3", " 1", "1,5", "1.2.3", "abc"] {
+            assert!(matches!(TokenAmount::parse(input, 6), Err(SwapError::InvalidAmount)), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parse_stops_at_u128() {
+
+        assert_eq!(TokenAmount::parse(&u128::MAX.to_string(), 0).unwrap().units, u128::MAX);
+        assert!(matches!(TokenAmount::parse("340282366920938463463374607431768211456", 0), Err(SwapError::AmountOverflow)));
+
+        // fine as a number, but not once it's in 18 decimal base units
+        assert!(matches!(TokenAmount::parse("340282366920938463464", 18), Err(SwapError::AmountOverflow)));
+    }
+
+    #[test]
+    fn display_round_trips_through_parse() {
+
+        for (input, printed) in [("1500.250000", "1500.25"), ("0.000001", "0.000001"), ("42", "42"), ("0", "0")] {
+            let amount = TokenAmount::parse(input, 6).unwrap();
+
+            assert_eq!(amount.to_string(), printed);
+            assert_eq!(TokenAmount::parse(&amount.to_string(), 6).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn rescale_rounds_down_and_checks_overflow() {
+
+        let amount = TokenAmount::parse("1.999999", 6).unwrap();
+
+        assert_eq!(amount.rescale(0).unwrap(), TokenAmount::from_units(1, 0));
+        assert_eq!(amount.rescale(18).unwrap(), TokenAmount::parse("1.999999", 18).unwrap());
+        assert!(matches!(TokenAmount::from_units(u128::MAX, 0).rescale(1), Err(SwapError::AmountOverflow)));
+    }
+
+    #[test]
+    fn amounts_in_different_units_dont_mix() {
+
+        let usdc = TokenAmount::whole(1, 6).unwrap();
+        let dai = TokenAmount::whole(1, 18).unwrap();
+
+        assert!(matches!(usdc.checked_add(dai), Err(SwapError::InvalidAmount)));
+        assert!(matches!(usdc.checked_sub(TokenAmount::whole(2, 6).unwrap()), Err(SwapError::AmountUnderflow)));
+        assert_eq!(usdc.partial_cmp(&dai), None);
+    }
+
+    #[test]
+    fn whole_checks_overflow() {
+
+        assert_eq!(TokenAmount::whole(50, 6).unwrap().units, 50_000_000);
+        assert_eq!(TokenAmount::whole(u64::MAX, 18).unwrap().units, u64::MAX as u128 * 10u128.pow(18));
+
+        // 10^39 is past u128 on its own, 10^30 times u64::MAX is past it too
+        assert!(matches!(TokenAmount::whole(1, 39), Err(SwapError::AmountOverflow)));
+        assert!(matches!(TokenAmount::whole(u64::MAX, 30), Err(SwapError::AmountOverflow)));
+    }
+}
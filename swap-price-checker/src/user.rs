@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::batch::BatchAuction;
+use crate::bridge::PendingTransfer;
+use crate::error::SwapError;
+use crate::exchange::Network;
+use crate::liquidity::LpPosition;
+use crate::store::{StoreError, close_out, read_users, write_users};
+use crate::token::{TokenAmount, TokenRegistry, TokenType};
+use crate::venue::Venue;
+
+#[derive(Clone)]
+pub struct User {
+
+    pub network: Network,    //network the user is currently swapping on
+    pub wallet_address: String,
+    pub balances: HashMap<Network, HashMap<TokenType, TokenAmount>>,   //balances held on each network
+    pub pending_transfers: Vec<PendingTransfer>,   //bridge transfers that haven't arrived yet
+    pub lp_positions: Vec<LpPosition>,             //liquidity the user has deposited into pools
+
+}
+
+pub struct UserDatabase {
+
+    pub users: Vec<User>,
+    pub block: u64,          //the bridge's block clock, saved with the users
+    path: Option<PathBuf>,   //file the users are kept in between runs, None to keep them in memory only
+}
+
+//Implementing user logic
+
+impl User {
+
+    //create a new user on a network, with random balances of every token registered on every network
+    pub fn new(network: Network, wallet_address: String, registry: &TokenRegistry) -> Self {
+
+        let mut balances = HashMap::new();
+    
+
+    
+        // Generate random balances for each token, a token with too many decimals for that starts empty
+        for token in registry.tokens() {
+
+            let amount = TokenAmount::whole(rand::random::<u64>() % 10_000, token.decimals) // Randomly assigns 0 to 9999 tokens
+                .unwrap_or(TokenAmount::zero(token.decimals));
+            balances.entry(token.network).or_insert_with(HashMap::new).insert(token.symbol.clone(), amount);
+        }
+
+        User {
+
+            network,
+            wallet_address,
+            balances,
+            pending_transfers: Vec::new(),
+            lp_positions: Vec::new(),
+        }
+    }
+
+    //move the user to another network, their balances on every network are kept
+    pub fn switch_network(&mut self, network: Network) {
+
+        self.network = network;
+    }
+
+    //balance of a token on a network, if the user holds any
+    pub fn balance(&self, network: Network, token: &TokenType) -> Option<TokenAmount> {
+
+        self.balances.get(&network).and_then(|balances| balances.get(token)).copied()
+    }
+
+    // Function to check if user has sufficient balance of a given token on a network
+    pub fn has_sufficient_balance(&self, network: Network, token: &TokenType, amount: TokenAmount) -> bool {
+
+
+        match self.balance(network, token) {
+
+            Some(balance) => balance >= amount,
+            None => false,
+        }
+    }
+
+
+    // Deduct the specified amount from the user's balance for a given token on a network
+    pub fn deduct_balance(&mut self, network: Network, token: &TokenType, amount: TokenAmount) -> Result<(), SwapError> {
+
+        if self.has_sufficient_balance(network, token, amount) {
+
+            if let Some(balance) = self.balances.get_mut(&network).and_then(|balances| balances.get_mut(token)) {
+
+                *balance = balance.checked_sub(amount)?;
+                Ok(())
+            } else {
+
+                Err(SwapError::BalanceNotFound)
+            }
+        } else {
+
+            Err(SwapError::InsufficientBalance)
+        }
+    }
+
+
+    // Add the specified amount to the user's balance for a given token on a network
+    pub fn add_balance(&mut self, network: Network, token: &TokenType, amount: TokenAmount) -> Result<(), SwapError> {
+
+        let balance = self.balances.entry(network).or_default()
+            .entry(token.clone()).or_insert(TokenAmount::zero(amount.decimals));
+
+        *balance = balance.checked_add(amount)?;
+        Ok(())
+    }    
+}
+
+//Managing multiple users
+
+impl UserDatabase {
+
+    //initialize a new empty db
+
+    pub fn new_db()  -> Self{
+
+        UserDatabase {users: Vec::new(), block: 0, path: None}
+    }
+
+    //load the users kept in a file, it's created on the first save if it doesn't exist yet.
+    //Their amounts have to be in the decimals the tokens are registered with
+
+    pub fn open(path: &Path, tokens: &TokenRegistry) -> Result<Self, StoreError> {
+
+        let (users, block) = read_users(path, tokens)?;
+        Ok(UserDatabase { users, block, path: Some(path.to_path_buf()) })
+    }
+
+    //write every user to the db's file, call after each change. Does nothing for an in-memory db
+
+    pub fn save(&self) -> Result<(), StoreError> {
+
+        match &self.path {
+            Some(path) => write_users(path, &self.users, self.block),
+            None => Ok(()),
+        }
+    }
+
+    //Save the users with what they hold in the market paid back into their balances, since the
+    //venues and auctions don't outlive the process. The users in the db keep their positions.
+
+    pub fn save_with_market(&self, venues: &[Box<dyn Venue>], auctions: &[BatchAuction]) -> Result<(), StoreError> {
+
+        match &self.path {
+            Some(path) => write_users(path, &close_out(&self.users, venues, auctions).map_err(StoreError::Market)?, self.block),
+            None => Ok(()),
+        }
+    }
+
+    //Add new user to the db
+
+    pub fn add_user(&mut self, user: User) {
+
+        self.users.push(user);
+    }
+
+    //fetch a user in db. Returns Option as user might not be in db
+
+    pub fn get_user_by_address(&mut self, address: &str) -> Option<&mut User> {
+
+        self.users.iter_mut().find(|user| user.wallet_address == address)
+    }
+
+    // Fetch a mutable reference to a user by wallet address. This allows you to update the user's details.
+
+    pub fn get_user_by_address_mut(&mut self, address: &str) -> Option<&mut User> {
+
+        self.users.iter_mut().find(|user| user.wallet_address == address)
+    }
+
+    // Remove a user from database
+    
+    pub fn remove_user_by_address(&mut self, address: &str) {
+
+        if let Some(index) = self.users.iter().position(|user| user.wallet_address == address) {
+
+            self.users.remove(index);
+        }
+    }
+
+}
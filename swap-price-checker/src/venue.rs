@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::error::SwapError;
+use crate::exchange::{ExchangeData, Network};
+use crate::gas::DEFAULT_GAS_UNITS;
+use crate::pricing::{PricingModel, SwapQuote};
+use crate::liquidity::{LiquidityDeposit, PositionValue, RangeDeposit, RangeOrder};
+use crate::strategy::{Strategy, StrategyParams};
+use crate::token::{TokenAmount, TokenType};
+
+//fees charged in one token on one venue, for a single swap or everything collected so far
+#[derive(Debug, Clone)]
+pub struct VenueFees {
+
+    pub venue_name: String,
+    pub token: TokenType,
+    pub lp_fee: TokenAmount,         //stays with the venue's liquidity providers
+    pub protocol_fee: TokenAmount,   //kept by the venue itself
+}
+
+//A liquidity venue the router can source a swap from. ExchangeData is the built-in one,
+//custom venues with their own pricing only need to implement this trait and derive Clone.
+pub trait Venue: VenueClone {
+
+    //name shown in routes and swap results
+    fn name(&self) -> &str;
+
+    //network the venue operates on
+    fn network(&self) -> Network;
+
+    //every (from, to) pair the venue can quote
+    fn supported_pairs(&self) -> Vec<(TokenType, TokenType)>;
+
+    //price a swap without changing anything
+    fn quote(&self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError>;
+
+    //gas one swap on the venue uses, a route pays it once per hop
+    fn gas_units(&self) -> u64 {
+
+        DEFAULT_GAS_UNITS
+    }
+
+    //how much of `to` the venue could pay out on the pair, None if it doesn't say
+    fn liquidity(&self, _from: &TokenType, _to: &TokenType) -> Option<TokenAmount> {
+
+        None
+    }
+
+    //execute a swap against the venue's liquidity and return what it actually paid out
+    fn execute(&mut self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError>;
+
+    //fees collected from every swap executed so far, one entry per token
+    fn collected_fees(&self) -> Vec<VenueFees> {
+
+        Vec::new()
+    }
+
+    //what depositing from_amount (plus the matching amount of `to`) into a pool would take and mint
+    fn quote_liquidity(&self, _from: &TokenType, _to: &TokenType, _from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //deposit into a pool and mint LP shares
+    fn add_liquidity(&mut self, _from: &TokenType, _to: &TokenType, _from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //burn LP shares and pay out their part of the pool
+    fn remove_liquidity(&mut self, _from: &TokenType, _to: &TokenType, _shares: u128) -> Result<PositionValue, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //what LP shares in a pool are worth right now
+    fn liquidity_value(&self, _from: &TokenType, _to: &TokenType, _shares: u128) -> Result<PositionValue, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //pool price of a pair that takes liquidity in price ranges, None for any other pool
+    fn range_price(&self, _from: &TokenType, _to: &TokenType) -> Option<f64> {
+
+        None
+    }
+
+    //open a range position in a concentrated pool
+    fn add_range_liquidity(&mut self, _from: &TokenType, _to: &TokenType, _order: &RangeOrder) -> Result<RangeDeposit, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //take liquidity out of a range position, fees included
+    fn remove_range_liquidity(&mut self, _from: &TokenType, _to: &TokenType, _position_id: u64, _liquidity: u128) -> Result<PositionValue, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //what a range position is worth right now
+    fn range_value(&self, _from: &TokenType, _to: &TokenType, _position_id: u64) -> Result<PositionValue, SwapError> {
+
+        Err(SwapError::LiquidityNotSupported)
+    }
+
+    //whether makers can place strategies on the venue
+    fn takes_strategies(&self) -> bool {
+
+        false
+    }
+
+    //maker strategies on the venue, filled by takers along with its own liquidity
+    fn strategies(&self) -> &[Strategy] {
+
+        &[]
+    }
+
+    //place a maker strategy, the venue holds its orders from then on
+    fn create_strategy(&mut self, _owner: &str, _params: &StrategyParams) -> Result<Strategy, SwapError> {
+
+        Err(SwapError::StrategiesNotSupported)
+    }
+
+    //take a maker's strategy off the venue with what its orders hold now
+    fn withdraw_strategy(&mut self, _owner: &str, _strategy_id: u64) -> Result<Strategy, SwapError> {
+
+        Err(SwapError::StrategiesNotSupported)
+    }
+}
+
+//Copies a venue behind a trait object, so an order can be tried on copies of the venues it
+//goes through first. Every venue that is Clone gets it.
+pub trait VenueClone {
+
+    fn clone_venue(&self) -> Box<dyn Venue>;
+}
+
+impl<T: Venue + Clone + 'static> VenueClone for T {
+
+    fn clone_venue(&self) -> Box<dyn Venue> {
+
+        Box::new(self.clone())
+    }
+}
+
+//Copies of the venues an order has traded on so far. Hops are executed on the copies, and
+//the venues are only replaced by them on `commit`; dropping it leaves them as they were, so
+//an order that fails half way changes nothing.
+pub struct StagedVenues {
+
+    staged: HashMap<usize, Box<dyn Venue>>,   //by position in the venues slice
+}
+
+impl StagedVenues {
+
+    pub fn new() -> Self {
+
+        StagedVenues { staged: HashMap::new() }
+    }
+
+    //execute a hop on the copy of a venue, taken the first time the order trades on it
+    pub fn execute(&mut self, venues: &[Box<dyn Venue>], venue_index: usize, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        let venue = venues.get(venue_index).ok_or(SwapError::TokenPairNotSupported)?;
+
+        self.staged.entry(venue_index)
+            .or_insert_with(|| venue.clone_venue())
+            .execute(from, to, amount_in)
+    }
+
+    //replace the venues with the copies the order traded on
+    pub fn commit(self, venues: &mut [Box<dyn Venue>]) {
+
+        for (venue_index, venue) in self.staged {
+            venues[venue_index] = venue;
+        }
+    }
+}
+
+impl Default for StagedVenues {
+
+    fn default() -> Self {
+
+        Self::new()
+    }
+}
+
+impl Venue for ExchangeData {
+
+    fn name(&self) -> &str {
+
+        self.exchange_name.as_str()
+    }
+
+    fn network(&self) -> Network {
+
+        self.supported_network
+    }
+
+    fn supported_pairs(&self) -> Vec<(TokenType, TokenType)> {
+
+        self.token_pairs.iter().map(|pair| (pair.from_token.clone(), pair.to_token.clone())).collect()
+    }
+
+    fn quote(&self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        self.simulate_swap(from, to, amount_in)
+    }
+
+    fn gas_units(&self) -> u64 {
+
+        self.gas_units
+    }
+
+    //the pool's reserve and what the maker strategies selling `to` hold
+    fn liquidity(&self, from: &TokenType, to: &TokenType) -> Option<TokenAmount> {
+
+        let pair = self.token_pairs.iter().find(|pair| &pair.from_token == from && &pair.to_token == to)?;
+
+        self.strategies.iter()
+            .filter_map(|strategy| strategy.order_for(from, to))
+            .try_fold(pair.to_reserve, |total, order| total.checked_add(order.amount).ok())
+    }
+
+    fn execute(&mut self, from: &TokenType, to: &TokenType, amount_in: TokenAmount) -> Result<SwapQuote, SwapError> {
+
+        self.execute_swap(from, to, amount_in)
+    }
+
+    fn quote_liquidity(&self, from: &TokenType, to: &TokenType, from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        self.token_pairs.iter()
+            .find(|pair| &pair.from_token == from && &pair.to_token == to)
+            .ok_or(SwapError::TokenPairNotSupported)?
+            .quote_liquidity(from_amount)
+    }
+
+    fn add_liquidity(&mut self, from: &TokenType, to: &TokenType, from_amount: TokenAmount) -> Result<LiquidityDeposit, SwapError> {
+
+        ExchangeData::add_liquidity(self, from, to, from_amount)
+    }
+
+    fn remove_liquidity(&mut self, from: &TokenType, to: &TokenType, shares: u128) -> Result<PositionValue, SwapError> {
+
+        ExchangeData::remove_liquidity(self, from, to, shares)
+    }
+
+    fn liquidity_value(&self, from: &TokenType, to: &TokenType, shares: u128) -> Result<PositionValue, SwapError> {
+
+        self.token_pairs.iter()
+            .find(|pair| &pair.from_token == from && &pair.to_token == to)
+            .ok_or(SwapError::TokenPairNotSupported)?
+            .liquidity_value(shares)
+    }
+
+    fn range_price(&self, from: &TokenType, to: &TokenType) -> Option<f64> {
+
+        self.token_pairs.iter()
+            .find(|pair| &pair.from_token == from && &pair.to_token == to)
+            .filter(|pair| matches!(pair.pricing, PricingModel::Concentrated(_)))
+            .map(|pair| pair.mid_price())
+    }
+
+    fn add_range_liquidity(&mut self, from: &TokenType, to: &TokenType, order: &RangeOrder) -> Result<RangeDeposit, SwapError> {
+
+        ExchangeData::add_range_liquidity(self, from, to, order)
+    }
+
+    fn remove_range_liquidity(&mut self, from: &TokenType, to: &TokenType, position_id: u64, liquidity: u128) -> Result<PositionValue, SwapError> {
+
+        ExchangeData::remove_range_liquidity(self, from, to, position_id, liquidity)
+    }
+
+    fn range_value(&self, from: &TokenType, to: &TokenType, position_id: u64) -> Result<PositionValue, SwapError> {
+
+        ExchangeData::range_value(self, from, to, position_id)
+    }
+
+    fn takes_strategies(&self) -> bool {
+
+        self.maker_strategies
+    }
+
+    fn strategies(&self) -> &[Strategy] {
+
+        &self.strategies
+    }
+
+    fn create_strategy(&mut self, owner: &str, params: &StrategyParams) -> Result<Strategy, SwapError> {
+
+        ExchangeData::create_strategy(self, owner, params)
+    }
+
+    fn withdraw_strategy(&mut self, owner: &str, strategy_id: u64) -> Result<Strategy, SwapError> {
+
+        ExchangeData::withdraw_strategy(self, owner, strategy_id)
+    }
+
+    fn collected_fees(&self) -> Vec<VenueFees> {
+
+        let mut tokens: Vec<&TokenType> = self.lp_fees.keys().chain(self.protocol_fees.keys()).collect();
+        tokens.sort();
+        tokens.dedup();
+
+        tokens.into_iter()
+            .map(|token| {
+                let decimals = self.lp_fees.get(token).or(self.protocol_fees.get(token)).unwrap().decimals;
+
+                VenueFees {
+                    venue_name: self.name().to_string(),
+                    token: token.clone(),
+                    lp_fee: self.lp_fees.get(token).copied().unwrap_or(TokenAmount::zero(decimals)),
+                    protocol_fee: self.protocol_fees.get(token).copied().unwrap_or(TokenAmount::zero(decimals)),
+                }
+            })
+            .collect()
+    }
+}